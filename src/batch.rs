@@ -0,0 +1,88 @@
+/// Name of the tag given to items that come without any.
+pub const UNTAGGED: &str = "c";
+
+/// A journal entry to be written by this run.
+pub struct Entry {
+    /// Index keys of the link; items sharing one are the same link.
+    pub keys: Vec<String>,
+    pub title: String,
+    pub site: String,
+    pub url: String,
+    /// Tag names, without `#` or brackets.
+    pub tags: Vec<String>,
+    pub properties: Vec<(String, String)>,
+    /// Title and URL of every item merged into this entry.
+    pub items: Vec<(String, String)>,
+}
+
+impl Entry {
+    pub fn new(keys: Vec<String>, title: String, site: String, url: String, tags: Vec<String>, properties: Vec<(String, String)>) -> Self {
+        let items = vec![(title.clone(), url.clone())];
+        Self { keys, title, site, url, tags, properties, items }
+    }
+
+    /// Whether other items of the run were folded into this one.
+    pub fn is_merged(&self) -> bool {
+        self.items.len() > 1
+    }
+
+    fn merge(&mut self, other: Entry) {
+        if title_rank(&other.title) > title_rank(&self.title) {
+            self.title = other.title;
+        }
+        for key in other.keys {
+            if !self.keys.contains(&key) {
+                self.keys.push(key);
+            }
+        }
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        // `c` only stands in for missing tags
+        if self.tags.len() > 1 {
+            self.tags.retain(|tag| tag != UNTAGGED);
+        }
+        for (name, value) in other.properties {
+            if !self.properties.iter().any(|(n, _)| *n == name) {
+                self.properties.push((name, value));
+            }
+        }
+        self.items.extend(other.items);
+    }
+}
+
+/// New entries of a run, with items for the same link merged into the
+/// first one: tags and properties are combined and the best title is kept.
+#[derive(Default)]
+pub struct Batch {
+    pub entries: Vec<Entry>,
+}
+
+impl Batch {
+    pub fn add(&mut self, entry: Entry) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|existing| existing.keys.iter().any(|key| entry.keys.contains(key)))
+        {
+            Some(existing) => existing.merge(entry),
+            None => self.entries.push(entry),
+        }
+    }
+}
+
+/// Real titles beat ones that are just a URL, which beat none; among real
+/// titles the longer one is usually the untruncated one.
+fn title_rank(title: &str) -> (u8, usize) {
+    let title = title.trim();
+    let kind = if title.is_empty() {
+        0
+    } else if title.starts_with("http") {
+        1
+    } else {
+        2
+    };
+    (kind, title.chars().count())
+}
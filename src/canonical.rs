@@ -0,0 +1,148 @@
+mod arxiv;
+mod bilibili;
+mod doi;
+mod github;
+mod medium;
+mod reddit;
+mod stackoverflow;
+mod substack;
+mod twitter;
+mod wechat;
+mod weibo;
+mod wikipedia;
+mod youtube;
+mod zhihu;
+
+use reqwest::Client;
+
+/// A link after site-specific canonicalization.
+#[derive(Debug, Default)]
+pub struct Canonical {
+    /// URL written to the journal.
+    pub url: String,
+    /// Identity of the linked entity, e.g. `youtube:<id>`, shared by every
+    /// form of the link. `None` when only the URL itself identifies the page.
+    pub key: Option<String>,
+    /// Block properties written under the journal entry, e.g. `timestamp:: 90`.
+    pub properties: Vec<(String, String)>,
+    /// Tags implied by the kind of link, e.g. `#[[vquest]]` for videos.
+    pub tags: Vec<String>,
+    /// Entity type such as `zhihu/answer`, mapped to a tag by `Settings::entity_tags`.
+    pub entity: Option<&'static str>,
+    /// Stable identifier such as `arxiv:2301.00001`, written as the `source::`
+    /// property so the entry can be queried by it.
+    pub id: Option<String>,
+}
+
+/// Runs the site rules over `url`. Returns `None` when no rule recognizes it.
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    youtube::canonicalize(url)
+        .or_else(|| bilibili::canonicalize(url))
+        .or_else(|| zhihu::canonicalize(url))
+        .or_else(|| twitter::canonicalize(url))
+        .or_else(|| weibo::canonicalize(url))
+        .or_else(|| wechat::canonicalize(url))
+        .or_else(|| arxiv::canonicalize(url))
+        .or_else(|| doi::canonicalize(url))
+        .or_else(|| github::canonicalize(url))
+        .or_else(|| reddit::canonicalize(url))
+        .or_else(|| stackoverflow::canonicalize(url))
+        .or_else(|| wikipedia::canonicalize(url))
+        .or_else(|| medium::canonicalize(url))
+        .or_else(|| substack::canonicalize(url))
+}
+
+/// Runs every rule's table of real-world cases and describes the ones whose
+/// canonical URL differs from the expected one.
+pub fn check_cases() -> Vec<String> {
+    let tables = [
+        reddit::CASES,
+        stackoverflow::CASES,
+        wikipedia::CASES,
+        medium::CASES,
+        substack::CASES,
+    ];
+    let mut failures = Vec::new();
+    for (input, expected) in tables.iter().flat_map(|table| table.iter()) {
+        let actual = canonicalize(input).map(|canonical| canonical.url);
+        if actual.as_deref() != Some(*expected) {
+            failures.push(format!("{}\n  expected: {}\n  actual:   {:?}", input, expected, actual));
+        }
+    }
+    failures
+}
+
+/// Like `canonicalize`, but share links are first expanded to the page they
+/// stand for. The short link stays a separate identity, so callers looking
+/// the link up should also look up `url` itself.
+pub async fn canonicalize_resolved(client: &Client, url: &str) -> Option<Canonical> {
+    let resolved = resolve(client, url).await;
+    if resolved == url {
+        return canonicalize(url);
+    }
+    Some(canonicalize(&resolved).unwrap_or(Canonical {
+        url: resolved,
+        ..Default::default()
+    }))
+}
+
+/// Returns `id` if it is a non-empty run of ASCII digits.
+fn numeric(id: &str) -> Option<String> {
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+        Some(id.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Expands share links that hide the real URL behind a redirect or inside
+/// the page. Links that are not short links, or fail to resolve, are
+/// returned unchanged.
+async fn resolve(client: &Client, url: &str) -> String {
+    if bilibili::is_short_link(url) {
+        if let Ok(resolved) = bilibili::resolve_short_link(client, url).await {
+            return resolved;
+        }
+    }
+    if reddit::is_short_link(url) {
+        if let Ok(resolved) = reddit::resolve_short_link(client, url).await {
+            return resolved;
+        }
+    }
+    if wechat::is_short_link(url) {
+        if let Ok(Some(resolved)) = wechat::resolve_short_link(client, url).await {
+            return resolved;
+        }
+    }
+    url.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cases() {
+        let failures = check_cases();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn unrelated_sites_are_left_alone() {
+        let urls = [
+            "https://shop-a.com/item-1234567890?source=google",
+            "https://shop-b.org/thing-1234567890?source=ads",
+            "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html",
+        ];
+        for url in urls {
+            assert!(canonicalize(url).is_none(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn oversized_timestamps_are_ignored() {
+        let canonical = canonicalize("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=9999999999999999h").unwrap();
+        assert_eq!(canonical.url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert!(canonical.properties.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use reqwest::Url;
+use super::Canonical;
+
+/// An arXiv paper, identified by its id without version.
+#[derive(Debug, PartialEq)]
+pub struct Paper {
+    pub id: String,
+}
+
+impl Paper {
+    /// Parses `arxiv.org/abs/<id>`, `/pdf/<id>[vN][.pdf]` and `/html/<id>` links,
+    /// with new-style (`2301.00001`) or old-style (`hep-th/9901001`) ids.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        if host != "arxiv.org" && host != "www.arxiv.org" && host != "export.arxiv.org" {
+            return None;
+        }
+        let path = url.path();
+        let rest = ["/abs/", "/pdf/", "/html/", "/format/"]
+            .iter()
+            .find_map(|prefix| path.strip_prefix(prefix))?;
+        Self::from_id(rest)
+    }
+
+    /// Reads an id with an optional version and `.pdf` suffix, e.g. `2301.00001v2.pdf`.
+    pub fn from_id(id: &str) -> Option<Self> {
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| {
+            Regex::new(r"^((?:\d{4}\.\d{4,5})|(?:[a-z\-]+(?:\.[A-Z]{2})?/\d{7}))(?:v\d+)?(?:\.pdf)?/?$").unwrap()
+        });
+        let captures = pattern.captures(id)?;
+        Some(Self { id: captures[1].to_owned() })
+    }
+
+    pub fn canonical_url(&self) -> String {
+        format!("https://arxiv.org/abs/{}", self.id)
+    }
+
+    pub fn key(&self) -> String {
+        format!("arxiv:{}", self.id)
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let paper = Paper::parse(url)?;
+    Some(Canonical {
+        url: paper.canonical_url(),
+        key: Some(paper.key()),
+        entity: Some("arxiv/paper"),
+        id: Some(format!("arxiv:{}", paper.id)),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,158 @@
+use reqwest::{Client, Url};
+use super::Canonical;
+
+const XOR_CODE: u64 = 23442827791579;
+const MASK_CODE: u64 = 2251799813685247;
+const MAX_AID: u64 = 1 << 51;
+const ALPHABET: &[u8] = b"FcwAPNKTMug3GV5Lj7EJnHpWsx4tb8haYeviqBz6rkCy12mUSDQX9RdoZf";
+
+const VIDEO_HOSTS: &[&str] = &["bilibili.com", "www.bilibili.com", "m.bilibili.com"];
+
+/// A Bilibili video reduced to its av id and part number.
+#[derive(Debug, PartialEq)]
+pub struct Video {
+    pub aid: u64,
+    pub part: Option<u32>,
+}
+
+impl Video {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+        let id = if host == "b23.tv" {
+            // b23.tv links only carry the id directly when shared as `b23.tv/BV...`
+            segments.next()?
+        } else if VIDEO_HOSTS.contains(&host.as_str()) {
+            match segments.next()? {
+                "video" => segments.next()?,
+                _ => return None,
+            }
+        } else {
+            return None;
+        };
+        let aid = parse_id(id)?;
+        let part = url
+            .query_pairs()
+            .find(|(k, _)| k == "p")
+            .and_then(|(_, v)| v.parse().ok())
+            .filter(|&p| p > 1);
+        Some(Self { aid, part })
+    }
+
+    pub fn bvid(&self) -> String {
+        av_to_bv(self.aid)
+    }
+
+    pub fn canonical_url(&self) -> String {
+        match self.part {
+            Some(part) => format!("https://www.bilibili.com/video/{}?p={}", self.bvid(), part),
+            None => format!("https://www.bilibili.com/video/{}", self.bvid()),
+        }
+    }
+
+    /// Identity of the video in its BV form; each part of a multi-part
+    /// video counts as its own.
+    pub fn key(&self) -> String {
+        match self.part {
+            Some(part) => format!("bilibili:{}?p={}", self.bvid(), part),
+            None => format!("bilibili:{}", self.bvid()),
+        }
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let video = Video::parse(url)?;
+    Some(Canonical {
+        url: video.canonical_url(),
+        key: Some(video.key()),
+        tags: vec!["#[[vquest]]".to_owned()],
+        ..Default::default()
+    })
+}
+
+/// Whether `url` is a `b23.tv` share link that has to be followed to find the video.
+pub fn is_short_link(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.eq_ignore_ascii_case("b23.tv")))
+        .unwrap_or(false)
+        && Video::parse(url).is_none()
+}
+
+/// Follows a `b23.tv` redirect and returns the URL it points to.
+pub async fn resolve_short_link(client: &Client, url: &str) -> Result<String, reqwest::Error> {
+    let res = client.get(url).send().await?;
+    Ok(res.url().to_string())
+}
+
+fn parse_id(id: &str) -> Option<u64> {
+    let (prefix, rest) = (id.get(..2)?, id.get(2..)?);
+    if prefix.eq_ignore_ascii_case("av") {
+        // larger ids have no BV form
+        return rest.parse().ok().filter(|&aid| aid < MAX_AID);
+    }
+    if prefix.eq_ignore_ascii_case("bv") && rest.len() == 10 {
+        // reject strings that only decode by accident
+        return bv_to_av(rest).filter(|&aid| aid < MAX_AID && av_to_bv(aid)[2..] == *rest);
+    }
+    None
+}
+
+pub fn av_to_bv(aid: u64) -> String {
+    let mut bytes = *b"BV1000000000";
+    let mut tmp = (MAX_AID | aid) ^ XOR_CODE;
+    let mut index = bytes.len() - 1;
+    while tmp > 0 {
+        bytes[index] = ALPHABET[(tmp % 58) as usize];
+        tmp /= 58;
+        index -= 1;
+    }
+    bytes.swap(3, 9);
+    bytes.swap(4, 7);
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Decodes the ten characters following `BV` back into an av id.
+pub fn bv_to_av(bv: &str) -> Option<u64> {
+    let mut bytes = format!("BV{}", bv).into_bytes();
+    bytes.swap(3, 9);
+    bytes.swap(4, 7);
+    let mut tmp: u64 = 0;
+    for b in &bytes[3..] {
+        let digit = ALPHABET.iter().position(|a| a == b)? as u64;
+        tmp = tmp.checked_mul(58)?.checked_add(digit)?;
+    }
+    Some((tmp & MASK_CODE) ^ XOR_CODE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn av_and_bv_ids_round_trip() {
+        assert_eq!(av_to_bv(170001), "BV17x411w7KC");
+        assert_eq!(bv_to_av("17x411w7KC"), Some(170001));
+        for aid in [1, 170001, 114514, MAX_AID - 1] {
+            assert_eq!(bv_to_av(&av_to_bv(aid)[2..]), Some(aid));
+        }
+    }
+
+    #[test]
+    fn ids_without_a_bv_form_are_rejected() {
+        assert_eq!(parse_id("av170001"), Some(170001));
+        assert_eq!(parse_id("av2251799813685248"), None);
+        // decodes, but to an id whose BV form is another
+        assert_eq!(parse_id("BV27x411w7KC"), None);
+    }
+
+    #[test]
+    fn parts_are_kept_apart() {
+        let first = Video::parse("https://www.bilibili.com/video/BV17x411w7KC?p=1").unwrap();
+        let third = Video::parse("https://www.bilibili.com/video/av170001?p=3").unwrap();
+        assert_eq!(first.key(), "bilibili:BV17x411w7KC");
+        assert_eq!(third.key(), "bilibili:BV17x411w7KC?p=3");
+        assert_eq!(third.canonical_url(), "https://www.bilibili.com/video/BV17x411w7KC?p=3");
+    }
+}
@@ -0,0 +1,42 @@
+use reqwest::Url;
+use super::{arxiv, Canonical};
+
+/// Prefix arXiv uses for the DOIs it mints, e.g. `10.48550/arXiv.2301.00001`.
+const ARXIV_DOI_PREFIX: &str = "10.48550/arxiv.";
+
+/// Parses `doi.org/<doi>` and `dx.doi.org/<doi>` links. DOIs are
+/// case-insensitive, so the DOI is lowercased.
+pub fn parse(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    if host != "doi.org" && host != "dx.doi.org" && host != "www.doi.org" {
+        return None;
+    }
+    let doi = percent_encoding::percent_decode_str(url.path().trim_start_matches('/'))
+        .decode_utf8()
+        .ok()?
+        .to_lowercase();
+    if doi.starts_with("10.") && doi.contains('/') {
+        Some(doi)
+    } else {
+        None
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let doi = parse(url)?;
+    // arXiv DOIs name the same paper as the arXiv link
+    if let Some(paper) = doi
+        .strip_prefix(ARXIV_DOI_PREFIX)
+        .and_then(arxiv::Paper::from_id)
+    {
+        return arxiv::canonicalize(&paper.canonical_url());
+    }
+    Some(Canonical {
+        url: format!("https://doi.org/{}", doi),
+        key: Some(format!("doi:{}", doi)),
+        entity: Some("doi/paper"),
+        id: Some(format!("doi:{}", doi)),
+        ..Default::default()
+    })
+}
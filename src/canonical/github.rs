@@ -0,0 +1,60 @@
+use reqwest::Url;
+use super::Canonical;
+
+/// First path segments that are GitHub pages rather than user or org names.
+const RESERVED: &[&str] = &[
+    "about", "apps", "collections", "enterprise", "events", "explore", "features",
+    "login", "marketplace", "notifications", "orgs", "pricing", "pulls", "issues",
+    "search", "settings", "sponsors", "topics", "trending",
+];
+
+/// A GitHub repository. Branch, commit, file and anchor variants of its links
+/// all name the same repository.
+#[derive(Debug, PartialEq)]
+pub struct Repo {
+    pub owner: String,
+    pub name: String,
+}
+
+impl Repo {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        if host != "github.com" && host != "www.github.com" {
+            return None;
+        }
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        let (owner, name) = match segments.as_slice() {
+            [owner, name] => (owner, name),
+            [owner, name, "tree" | "blob", ..] => (owner, name),
+            _ => return None,
+        };
+        if RESERVED.contains(&owner.to_lowercase().as_str()) {
+            return None;
+        }
+        let name = name.strip_suffix(".git").unwrap_or(name);
+        Some(Self {
+            owner: owner.to_lowercase(),
+            name: name.to_lowercase(),
+        })
+    }
+
+    pub fn canonical_url(&self) -> String {
+        format!("https://github.com/{}/{}", self.owner, self.name)
+    }
+
+    pub fn key(&self) -> String {
+        format!("github:{}/{}", self.owner, self.name)
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let repo = Repo::parse(url)?;
+    Some(Canonical {
+        url: repo.canonical_url(),
+        key: Some(repo.key()),
+        entity: Some("github/repo"),
+        id: Some(format!("github:{}/{}", repo.owner, repo.name)),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,71 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use reqwest::Url;
+use super::Canonical;
+
+/// Publications on custom domains that are served by Medium.
+const CUSTOM_DOMAINS: &[&str] = &[
+    "towardsdatascience.com", "betterprogramming.pub", "levelup.gitconnected.com",
+    "uxdesign.cc", "blog.bitsrc.io", "javascript.plainenglish.io", "proandroiddev.com",
+];
+
+/// Input -> canonical URL cases, checked by `parser-rs check-rules`.
+pub const CASES: &[(&str, &str)] = &[
+    (
+        "https://medium.com/@karpathy/software-2-0-a64152b37c35?source=rss----1",
+        "https://medium.com/@karpathy/software-2-0-a64152b37c35",
+    ),
+    (
+        "https://karpathy.medium.com/software-2-0-a64152b37c35?source=friends_link&sk=1b2c3d",
+        "https://karpathy.medium.com/software-2-0-a64152b37c35",
+    ),
+    (
+        "https://towardsdatascience.com/transformers-141e32e69591?gi=4a1b2c3d4e5f",
+        "https://towardsdatascience.com/transformers-141e32e69591",
+    ),
+];
+
+/// A Medium post, identified by the hex id at the end of its slug.
+#[derive(Debug, PartialEq)]
+pub struct Post {
+    pub url: String,
+    pub id: String,
+}
+
+impl Post {
+    /// Parses posts on medium.com, its subdomains and known custom domains.
+    /// Other custom domains are left alone: their slugs and `source=`
+    /// parameters look like those of many unrelated sites.
+    pub fn parse(url: &str) -> Option<Self> {
+        let mut url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let is_medium = host == "medium.com"
+            || host.ends_with(".medium.com")
+            || CUSTOM_DOMAINS.contains(&host.as_str());
+        if !is_medium {
+            return None;
+        }
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| Regex::new(r"(?:-|^/p/)([0-9a-f]{10,12})/?$").unwrap());
+        let id = pattern.captures(url.path())?[1].to_owned();
+        url.set_query(None);
+        url.set_fragment(None);
+        let url = url.to_string().trim_end_matches('/').to_owned();
+        Some(Self { url, id })
+    }
+
+    /// Posts move between publications and custom domains, the id stays.
+    pub fn key(&self) -> String {
+        format!("medium:{}", self.id)
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let post = Post::parse(url)?;
+    Some(Canonical {
+        key: Some(post.key()),
+        url: post.url,
+        entity: Some("medium/post"),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,110 @@
+use reqwest::{Client, Url};
+use super::Canonical;
+
+const HOSTS: &[&str] = &[
+    "reddit.com", "www.reddit.com", "old.reddit.com", "new.reddit.com",
+    "np.reddit.com", "m.reddit.com", "i.reddit.com",
+];
+
+/// Input -> canonical URL cases, checked by `parser-rs check-rules`.
+pub const CASES: &[(&str, &str)] = &[
+    (
+        "https://old.reddit.com/r/IAmA/comments/z1c9z/i_am_barack_obama_president_of_the_united_states/",
+        "https://www.reddit.com/r/iama/comments/z1c9z",
+    ),
+    (
+        "https://www.reddit.com/r/IAmA/comments/z1c9z/?utm_source=share&utm_medium=web2x&context=3",
+        "https://www.reddit.com/r/iama/comments/z1c9z",
+    ),
+    (
+        "https://np.reddit.com/r/IAmA/comments/z1c9z/i_am_barack_obama_president_of_the_united_states/c60o0iw/",
+        "https://www.reddit.com/r/iama/comments/z1c9z",
+    ),
+    ("https://redd.it/z1c9z", "https://www.reddit.com/comments/z1c9z"),
+    ("https://m.reddit.com/r/rust/", "https://www.reddit.com/r/rust"),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Entity {
+    /// The subreddit is unknown for `redd.it/<id>` links.
+    Post { subreddit: Option<String>, id: String },
+    Subreddit(String),
+}
+
+impl Entity {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        if host == "redd.it" {
+            return match segments.as_slice() {
+                [id] => Some(Self::Post { subreddit: None, id: id.to_lowercase() }),
+                _ => None,
+            };
+        }
+        if !HOSTS.contains(&host.as_str()) {
+            return None;
+        }
+        let entity = match segments.as_slice() {
+            ["r", subreddit, "comments", id, ..] => Self::Post {
+                subreddit: Some(subreddit.to_lowercase()),
+                id: id.to_lowercase(),
+            },
+            ["comments", id, ..] => Self::Post { subreddit: None, id: id.to_lowercase() },
+            ["r", subreddit] => Self::Subreddit(subreddit.to_lowercase()),
+            _ => return None,
+        };
+        Some(entity)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Post { .. } => "reddit/post",
+            Self::Subreddit(_) => "reddit/subreddit",
+        }
+    }
+
+    pub fn canonical_url(&self) -> String {
+        match self {
+            Self::Post { subreddit: Some(subreddit), id } => {
+                format!("https://www.reddit.com/r/{}/comments/{}", subreddit, id)
+            }
+            Self::Post { subreddit: None, id } => format!("https://www.reddit.com/comments/{}", id),
+            Self::Subreddit(subreddit) => format!("https://www.reddit.com/r/{}", subreddit),
+        }
+    }
+
+    /// Identity of the entity; a post is the same with or without its subreddit.
+    pub fn key(&self) -> String {
+        match self {
+            Self::Post { id, .. } => format!("reddit/post:{}", id),
+            Self::Subreddit(subreddit) => format!("reddit/r:{}", subreddit.to_lowercase()),
+        }
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let entity = Entity::parse(url)?;
+    Some(Canonical {
+        url: entity.canonical_url(),
+        key: Some(entity.key()),
+        entity: Some(entity.kind()),
+        ..Default::default()
+    })
+}
+
+/// Whether `url` is a `/r/<sub>/s/<token>` share link, which only redirects
+/// to the post.
+pub fn is_short_link(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    let is_reddit = url.host_str().is_some_and(|host| HOSTS.contains(&host.to_lowercase().as_str()));
+    let segments: Vec<&str> = url.path_segments().map(|s| s.filter(|s| !s.is_empty()).collect()).unwrap_or_default();
+    is_reddit && matches!(segments.as_slice(), ["r", _, "s", _])
+}
+
+pub async fn resolve_short_link(client: &Client, url: &str) -> Result<String, reqwest::Error> {
+    let res = client.get(url).send().await?;
+    Ok(res.url().to_string())
+}
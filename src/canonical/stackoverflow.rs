@@ -0,0 +1,87 @@
+use reqwest::Url;
+use super::{numeric, Canonical};
+
+/// Stack Exchange sites that live outside `*.stackexchange.com`.
+const HOSTS: &[&str] = &[
+    "stackoverflow.com", "superuser.com", "serverfault.com", "askubuntu.com",
+    "mathoverflow.net", "stackapps.com",
+];
+
+/// Input -> canonical URL cases, checked by `parser-rs check-rules`.
+pub const CASES: &[(&str, &str)] = &[
+    (
+        "https://stackoverflow.com/questions/11227809/why-is-processing-a-sorted-array-faster-than-processing-an-unsorted-array",
+        "https://stackoverflow.com/questions/11227809",
+    ),
+    (
+        "https://stackoverflow.com/q/11227809",
+        "https://stackoverflow.com/questions/11227809",
+    ),
+    ("https://stackoverflow.com/a/11227902/", "https://stackoverflow.com/a/11227902"),
+    (
+        "https://stackoverflow.com/questions/11227809/why-is-processing-a-sorted-array-faster-than-processing-an-unsorted-array/11227902#11227902",
+        "https://stackoverflow.com/a/11227902",
+    ),
+    (
+        "https://unix.stackexchange.com/questions/4126/what-is-the-exact-difference-between-a-terminal-a-shell-a-tty-and-a-con?noredirect=1",
+        "https://unix.stackexchange.com/questions/4126",
+    ),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Entity {
+    Question { host: String, id: String },
+    Answer { host: String, id: String },
+}
+
+impl Entity {
+    /// Parses question and answer links. Slugs and the user id in `/q/<id>/<user>`
+    /// share links are ignored.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host).to_owned();
+        if !HOSTS.contains(&host.as_str()) && !host.ends_with(".stackexchange.com") {
+            return None;
+        }
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        let entity = match segments.as_slice() {
+            ["questions", _, _, answer] => Self::Answer { host, id: numeric(answer)? },
+            ["questions", id, ..] | ["q", id, ..] => Self::Question { host, id: numeric(id)? },
+            ["a", id, ..] => Self::Answer { host, id: numeric(id)? },
+            _ => return None,
+        };
+        Some(entity)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Question { .. } => "stackoverflow/question",
+            Self::Answer { .. } => "stackoverflow/answer",
+        }
+    }
+
+    pub fn canonical_url(&self) -> String {
+        match self {
+            Self::Question { host, id } => format!("https://{}/questions/{}", host, id),
+            Self::Answer { host, id } => format!("https://{}/a/{}", host, id),
+        }
+    }
+
+    pub fn key(&self) -> String {
+        match self {
+            Self::Question { host, id } => format!("{}/q/{}", host, id),
+            Self::Answer { host, id } => format!("{}/a/{}", host, id),
+        }
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let entity = Entity::parse(url)?;
+    Some(Canonical {
+        url: entity.canonical_url(),
+        key: Some(entity.key()),
+        entity: Some(entity.kind()),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,62 @@
+use reqwest::Url;
+use super::Canonical;
+
+/// Input -> canonical URL cases, checked by `parser-rs check-rules`.
+pub const CASES: &[(&str, &str)] = &[
+    (
+        "https://astralcodexten.substack.com/p/still-alive?utm_source=substack&utm_medium=email",
+        "https://astralcodexten.substack.com/p/still-alive",
+    ),
+    (
+        "https://www.astralcodexten.com/p/still-alive?r=1x2y3&utm_campaign=post&utm_medium=web&utm_source=substack",
+        "https://www.astralcodexten.com/p/still-alive",
+    ),
+    (
+        "https://astralcodexten.substack.com/p/still-alive/comments#comment-1234567",
+        "https://astralcodexten.substack.com/p/still-alive",
+    ),
+];
+
+/// A Substack post, on `*.substack.com` or on a custom domain.
+#[derive(Debug, PartialEq)]
+pub struct Post {
+    pub host: String,
+    pub slug: String,
+}
+
+impl Post {
+    /// Custom domains are recognized by the `utm_source=substack` parameter
+    /// Substack adds to every shared link.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let is_substack = host.ends_with(".substack.com")
+            || url.query_pairs().any(|(k, v)| k == "utm_source" && v.contains("substack"));
+        if !is_substack {
+            return None;
+        }
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        match segments.as_slice() {
+            ["p", slug, ..] => Some(Self { host, slug: slug.to_string() }),
+            _ => None,
+        }
+    }
+
+    pub fn canonical_url(&self) -> String {
+        format!("https://{}/p/{}", self.host, self.slug)
+    }
+
+    pub fn key(&self) -> String {
+        format!("substack:{}/{}", self.host, self.slug)
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let post = Post::parse(url)?;
+    Some(Canonical {
+        url: post.canonical_url(),
+        key: Some(post.key()),
+        entity: Some("substack/post"),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,119 @@
+use reqwest::Url;
+use super::{numeric, Canonical};
+
+const HOSTS: &[&str] = &[
+    "twitter.com",
+    "mobile.twitter.com",
+    "x.com",
+    "mobile.x.com",
+    "fxtwitter.com",
+    "vxtwitter.com",
+    "fixupx.com",
+    "fixvx.com",
+    "twittpr.com",
+    "xcancel.com",
+];
+
+/// First path segments that are app pages rather than user names.
+const RESERVED: &[&str] = &[
+    "i", "home", "explore", "search", "hashtag", "settings", "notifications",
+    "messages", "intent", "share", "compose", "login", "signup", "tos", "privacy",
+];
+
+/// Profile tabs that still identify the same person.
+const PROFILE_TABS: &[&str] = &["with_replies", "media", "likes", "highlights"];
+
+#[derive(Debug, PartialEq)]
+pub enum Entity {
+    /// The author is unknown for `/i/web/status/<id>` links.
+    Status { user: Option<String>, id: String },
+    Profile(String),
+}
+
+impl Entity {
+    /// Parses a link on twitter.com, x.com or one of their mirror front-ends.
+    /// Returns `None` for pages that are neither a status nor a profile.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        if !is_twitter_host(url.host_str()?) {
+            return None;
+        }
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        let entity = match segments.as_slice() {
+            ["i", "web", "status", id, ..] | ["i", "status", id, ..] => Self::Status {
+                user: None,
+                id: numeric(id)?,
+            },
+            [user, "status" | "statuses", id, ..] if is_user_name(user) => Self::Status {
+                user: Some(user.to_lowercase()),
+                id: numeric(id)?,
+            },
+            [user] if is_user_name(user) => Self::Profile(user.to_lowercase()),
+            [user, tab] if is_user_name(user) && PROFILE_TABS.contains(tab) => {
+                Self::Profile(user.to_lowercase())
+            }
+            _ => return None,
+        };
+        Some(entity)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Status { .. } => "twitter/status",
+            Self::Profile(_) => "twitter/profile",
+        }
+    }
+
+    pub fn canonical_url(&self) -> String {
+        match self {
+            Self::Status { user: Some(user), id } => format!("https://x.com/{}/status/{}", user, id),
+            Self::Status { user: None, id } => format!("https://x.com/i/web/status/{}", id),
+            // profiles are saved with replies so the whole timeline is visible
+            Self::Profile(user) => format!("https://x.com/{}/with_replies", user),
+        }
+    }
+
+    /// Identity of the entity: statuses by id alone, profiles by lowercased name.
+    pub fn key(&self) -> String {
+        match self {
+            Self::Status { id, .. } => format!("{}:{}", self.kind(), id),
+            Self::Profile(user) => format!("{}:{}", self.kind(), user.to_lowercase()),
+        }
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    match Entity::parse(url) {
+        Some(entity) => Some(Canonical {
+            url: entity.canonical_url(),
+            key: Some(entity.key()),
+            entity: Some(entity.kind()),
+            ..Default::default()
+        }),
+        None => {
+            // other pages (lists, spaces, ...) only move to x.com without the query
+            let mut url = Url::parse(url).ok()?;
+            if !is_twitter_host(url.host_str()?) {
+                return None;
+            }
+            url.set_host(Some("x.com")).ok()?;
+            url.set_query(None);
+            Some(Canonical {
+                url: url.to_string(),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+fn is_twitter_host(host: &str) -> bool {
+    let host = host.to_lowercase();
+    let host = host.trim_start_matches("www.");
+    HOSTS.contains(&host) || host == "nitter.net" || host.starts_with("nitter.")
+}
+
+fn is_user_name(name: &str) -> bool {
+    !RESERVED.contains(&name.to_lowercase().as_str())
+        && !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
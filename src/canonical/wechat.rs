@@ -0,0 +1,95 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use reqwest::{Client, Url};
+use super::Canonical;
+
+/// Query parameters that identify an article; everything else (`chksm`,
+/// `scene`, `sessionid`, ...) changes with every share.
+const IDENTIFYING_PARAMS: &[&str] = &["__biz", "mid", "idx", "sn"];
+
+/// A WeChat public-account article, either by its identifying parameters or
+/// by the opaque token of a `mp.weixin.qq.com/s/<token>` short link.
+#[derive(Debug, PartialEq)]
+pub enum Article {
+    Params { biz: String, mid: String, idx: String, sn: String },
+    Token(String),
+}
+
+impl Article {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        if !url.host_str()?.eq_ignore_ascii_case("mp.weixin.qq.com") {
+            return None;
+        }
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        match segments.as_slice() {
+            ["s"] => {
+                // `__biz` is base64, so read the raw query to keep `+` and `=` intact
+                let mut params = [None, None, None, None];
+                for pair in url.query()?.split('&') {
+                    let Some((name, value)) = pair.split_once('=') else {
+                        continue;
+                    };
+                    if let Some(index) = IDENTIFYING_PARAMS.iter().position(|p| *p == name) {
+                        params[index] = Some(value.to_owned());
+                    }
+                }
+                let [biz, mid, idx, sn] = params;
+                Some(Self::Params { biz: biz?, mid: mid?, idx: idx?, sn: sn? })
+            }
+            ["s", token] => Some(Self::Token(token.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn canonical_url(&self) -> String {
+        match self {
+            Self::Params { biz, mid, idx, sn } => format!(
+                "https://mp.weixin.qq.com/s?__biz={}&mid={}&idx={}&sn={}",
+                biz, mid, idx, sn
+            ),
+            Self::Token(token) => format!("https://mp.weixin.qq.com/s/{}", token),
+        }
+    }
+
+    /// `sn` is a signature over the article, so it identifies it on its own.
+    pub fn key(&self) -> String {
+        match self {
+            Self::Params { sn, .. } => format!("wechat:{}", sn),
+            Self::Token(token) => format!("wechat/s:{}", token),
+        }
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let article = Article::parse(url)?;
+    Some(Canonical {
+        url: article.canonical_url(),
+        key: Some(article.key()),
+        entity: Some("wechat/article"),
+        ..Default::default()
+    })
+}
+
+pub fn is_short_link(url: &str) -> bool {
+    matches!(Article::parse(url), Some(Article::Token(_)))
+}
+
+/// Fetches a short-token article and rebuilds its parameter URL from the
+/// `msg_link` embedded in the page.
+pub async fn resolve_short_link(client: &Client, url: &str) -> Result<Option<String>, reqwest::Error> {
+    let body = client.get(url).send().await?.text().await?;
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r"__biz=([A-Za-z0-9+/=]+)&(?:amp;)?mid=(\d+)&(?:amp;)?idx=(\d+)&(?:amp;)?sn=([0-9a-f]+)").unwrap()
+    });
+    Ok(pattern.captures(&body).map(|captures| {
+        Article::Params {
+            biz: captures[1].to_owned(),
+            mid: captures[2].to_owned(),
+            idx: captures[3].to_owned(),
+            sn: captures[4].to_owned(),
+        }
+        .canonical_url()
+    }))
+}
@@ -0,0 +1,132 @@
+use reqwest::Url;
+use super::Canonical;
+
+const HOSTS: &[&str] = &["weibo.com", "www.weibo.com", "m.weibo.cn", "weibo.cn", "www.weibo.cn", "m.weibo.com"];
+const ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// A Weibo post. Mobile links address it by its numeric id, desktop links by
+/// the base62 `mid` of that same id.
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    pub id: u64,
+}
+
+impl Status {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        if !HOSTS.contains(&host.as_str()) {
+            return None;
+        }
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        match segments.as_slice() {
+            ["status" | "detail" | "comment" | "repost", id] => Some(Self { id: parse_id(id)? }),
+            // `weibo.com/<uid>/<mid>` and `m.weibo.cn/<uid>/<id>`
+            [uid, id] if uid.chars().all(|c| c.is_ascii_digit()) => Some(Self { id: parse_id(id)? }),
+            _ => None,
+        }
+    }
+
+    pub fn canonical_url(&self) -> String {
+        format!("https://m.weibo.cn/status/{}", self.id)
+    }
+
+    pub fn key(&self) -> String {
+        format!("weibo:{}", self.id)
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let status = Status::parse(url)?;
+    Some(Canonical {
+        url: status.canonical_url(),
+        key: Some(status.key()),
+        entity: Some("weibo/status"),
+        ..Default::default()
+    })
+}
+
+/// Accepts a 16-digit numeric id or a 9-character base62 mid.
+fn parse_id(id: &str) -> Option<u64> {
+    if id.len() == 16 && id.chars().all(|c| c.is_ascii_digit()) {
+        return id.parse().ok();
+    }
+    if id.len() == 9 && id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        // page names such as `followers` are valid base62 too
+        if id.chars().all(|c| c.is_ascii_lowercase()) {
+            return None;
+        }
+        return decode_mid(id).filter(|&decoded| encode_mid(decoded) == id);
+    }
+    None
+}
+
+/// Encodes a numeric id as a mid: from the right, each group of 7 decimal
+/// digits becomes 4 base62 characters.
+pub fn encode_mid(id: u64) -> String {
+    let digits = id.to_string();
+    let mut groups = Vec::new();
+    let mut end = digits.len();
+    while end > 0 {
+        let start = end.saturating_sub(7);
+        let mut value: u64 = digits[start..end].parse().unwrap_or_default();
+        let mut group = Vec::new();
+        while value > 0 {
+            group.push(ALPHABET[(value % 62) as usize]);
+            value /= 62;
+        }
+        // every group but the leftmost is zero-padded
+        if start > 0 {
+            group.resize(4, b'0');
+        }
+        group.reverse();
+        groups.push(String::from_utf8_lossy(&group).into_owned());
+        end = start;
+    }
+    groups.reverse();
+    groups.concat()
+}
+
+/// Decodes a mid back into its numeric id, the inverse of `encode_mid`.
+pub fn decode_mid(mid: &str) -> Option<u64> {
+    let bytes = mid.as_bytes();
+    let mut digits = String::new();
+    let mut end = bytes.len();
+    let mut groups = Vec::new();
+    while end > 0 {
+        let start = end.saturating_sub(4);
+        let mut value: u64 = 0;
+        for b in &bytes[start..end] {
+            value = value * 62 + ALPHABET.iter().position(|a| a == b)? as u64;
+        }
+        let group = if start > 0 { format!("{:07}", value) } else { value.to_string() };
+        groups.push(group);
+        end = start;
+    }
+    for group in groups.iter().rev() {
+        digits.push_str(group);
+    }
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_and_mids_round_trip() {
+        assert_eq!(encode_mid(4747733340539473), "Lk1Lg2glb");
+        assert_eq!(decode_mid("Lk1Lg2glb"), Some(4747733340539473));
+        // groups of zeros are padded, not dropped
+        assert_eq!(encode_mid(4000000000000001), "E00000001");
+        assert_eq!(decode_mid("E00000001"), Some(4000000000000001));
+    }
+
+    #[test]
+    fn mobile_and_desktop_links_are_one_status() {
+        let mobile = Status::parse("https://m.weibo.cn/status/4747733340539473").unwrap();
+        let desktop = Status::parse("https://weibo.com/1234567890/Lk1Lg2glb").unwrap();
+        assert_eq!(mobile, desktop);
+        assert_eq!(Status::parse("https://weibo.com/1234567890/followers"), None);
+    }
+}
@@ -0,0 +1,83 @@
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+use super::Canonical;
+
+/// Input -> canonical URL cases, checked by `parser-rs check-rules`.
+pub const CASES: &[(&str, &str)] = &[
+    (
+        "https://en.m.wikipedia.org/wiki/Rust_(programming_language)",
+        "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+    ),
+    (
+        "https://en.wikipedia.org/wiki/Rust%20(programming%20language)#History",
+        "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+    ),
+    (
+        "https://en.wikipedia.org/w/index.php?title=rust_(programming_language)&action=history",
+        "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+    ),
+    (
+        "https://zh.wikipedia.org/wiki/%E5%8C%97%E4%BA%AC%E5%B8%82",
+        "https://zh.wikipedia.org/wiki/北京市",
+    ),
+    ("https://de.m.wikipedia.org/wiki/AC/DC", "https://de.wikipedia.org/wiki/AC/DC"),
+];
+
+/// A Wikipedia article: language edition plus normalized title.
+#[derive(Debug, PartialEq)]
+pub struct Article {
+    pub lang: String,
+    pub title: String,
+}
+
+impl Article {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let lang = host.strip_suffix(".wikipedia.org")?;
+        let lang = lang.strip_suffix(".m").unwrap_or(lang);
+        if lang.is_empty() || lang.contains('.') || lang == "www" {
+            return None;
+        }
+        let raw = match url.path().strip_prefix("/wiki/") {
+            Some(title) => title.to_owned(),
+            None if url.path() == "/w/index.php" => {
+                url.query_pairs().find(|(k, _)| k == "title")?.1.into_owned()
+            }
+            None => return None,
+        };
+        let title = normalize_title(&percent_decode_str(&raw).decode_utf8().ok()?);
+        if title.is_empty() {
+            return None;
+        }
+        Some(Self { lang: lang.to_owned(), title })
+    }
+
+    pub fn canonical_url(&self) -> String {
+        format!("https://{}.wikipedia.org/wiki/{}", self.lang, self.title)
+    }
+
+    pub fn key(&self) -> String {
+        format!("wikipedia:{}/{}", self.lang, self.title)
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let article = Article::parse(url)?;
+    Some(Canonical {
+        url: article.canonical_url(),
+        key: Some(article.key()),
+        entity: Some("wikipedia/article"),
+        ..Default::default()
+    })
+}
+
+/// MediaWiki titles use underscores for spaces and an uppercase first letter.
+fn normalize_title(title: &str) -> String {
+    let title = title.trim().replace(' ', "_");
+    let mut chars = title.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,108 @@
+use reqwest::Url;
+use super::Canonical;
+
+const VIDEO_HOSTS: &[&str] = &[
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "music.youtube.com",
+    "youtube-nocookie.com",
+    "www.youtube-nocookie.com",
+];
+
+/// A YouTube video link reduced to its id and optional start time.
+#[derive(Debug, PartialEq)]
+pub struct Video {
+    pub id: String,
+    pub timestamp: Option<u64>,
+}
+
+impl Video {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+        let id = if host == "youtu.be" || host == "www.youtu.be" {
+            segments.next()?.to_owned()
+        } else if VIDEO_HOSTS.contains(&host.as_str()) {
+            match segments.next()? {
+                "watch" => url.query_pairs().find(|(k, _)| k == "v")?.1.into_owned(),
+                "shorts" | "embed" | "live" | "v" => segments.next()?.to_owned(),
+                _ => return None,
+            }
+        } else {
+            return None;
+        };
+        if !is_video_id(&id) {
+            return None;
+        }
+
+        // `t` on watch/youtu.be links, `start` on embeds, `#t=` on old links
+        let timestamp = url
+            .query_pairs()
+            .find(|(k, _)| k == "t" || k == "start")
+            .map(|(_, v)| v.into_owned())
+            .or_else(|| url.fragment().and_then(|f| f.strip_prefix("t=")).map(str::to_owned))
+            .and_then(|t| parse_timestamp(&t))
+            .filter(|&t| t > 0);
+
+        Some(Self { id, timestamp })
+    }
+
+    pub fn canonical_url(&self) -> String {
+        format!("https://www.youtube.com/watch?v={}", self.id)
+    }
+
+    /// Identity of the video, whatever form of link it was saved as.
+    pub fn key(&self) -> String {
+        format!("youtube:{}", self.id)
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let video = Video::parse(url)?;
+    let mut properties = Vec::new();
+    if let Some(timestamp) = video.timestamp {
+        properties.push(("timestamp".to_owned(), timestamp.to_string()));
+    }
+    Some(Canonical {
+        url: video.canonical_url(),
+        key: Some(video.key()),
+        properties,
+        tags: vec!["#[[vquest]]".to_owned()],
+        ..Default::default()
+    })
+}
+
+fn is_video_id(id: &str) -> bool {
+    id.len() == 11 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parses `90`, `90s` and `1h2m3s` style timestamps into seconds. `None`
+/// for anything else, including timestamps too large to count.
+fn parse_timestamp(value: &str) -> Option<u64> {
+    if let Ok(seconds) = value.parse() {
+        return Some(seconds);
+    }
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: u64 = number.parse().ok()?;
+        number.clear();
+        let seconds = match c {
+            'h' => n.checked_mul(3600)?,
+            'm' => n.checked_mul(60)?,
+            's' => n,
+            _ => return None,
+        };
+        total = total.checked_add(seconds)?;
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(total)
+}
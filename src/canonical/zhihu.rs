@@ -0,0 +1,92 @@
+use reqwest::Url;
+use super::{numeric, Canonical};
+
+/// The kinds of Zhihu page we save, each reduced to its identifying id or slug.
+#[derive(Debug, PartialEq)]
+pub enum Entity {
+    /// The question id is unknown for `www.zhihu.com/answer/<id>` short links.
+    Answer { question: Option<String>, id: String },
+    Question(String),
+    Article(String),
+    Pin(String),
+    Column(String),
+    People(String),
+}
+
+impl Entity {
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        let entity = match (host.as_str(), segments.as_slice()) {
+            ("oia.zhihu.com", ["answers", id, ..]) => Self::Answer { question: None, id: numeric(id)? },
+            ("oia.zhihu.com", ["questions", id, ..]) => Self::Question(numeric(id)?),
+            ("oia.zhihu.com", ["articles", id, ..]) => Self::Article(numeric(id)?),
+            ("oia.zhihu.com", ["pins", id, ..]) => Self::Pin(numeric(id)?),
+            ("oia.zhihu.com", ["people", slug, ..]) => Self::People(slug.to_string()),
+            ("zhuanlan.zhihu.com", ["p", id, ..]) => Self::Article(numeric(id)?),
+            ("zhuanlan.zhihu.com", [slug, ..]) => Self::Column(slug.to_string()),
+            ("zhihu.com" | "www.zhihu.com" | "m.zhihu.com", path) => match path {
+                ["question", question, "answer", id, ..] => Self::Answer {
+                    question: Some(numeric(question)?),
+                    id: numeric(id)?,
+                },
+                ["question", id, ..] => Self::Question(numeric(id)?),
+                ["answer", id, ..] => Self::Answer { question: None, id: numeric(id)? },
+                ["pin", id, ..] => Self::Pin(numeric(id)?),
+                ["column", slug, ..] => Self::Column(slug.to_string()),
+                ["people", slug, ..] => Self::People(slug.to_string()),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(entity)
+    }
+
+    /// Entity type used for tagging, see `Settings::entity_tags`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Answer { .. } => "zhihu/answer",
+            Self::Question(_) => "zhihu/question",
+            Self::Article(_) => "zhihu/article",
+            Self::Pin(_) => "zhihu/pin",
+            Self::Column(_) => "zhihu/column",
+            Self::People(_) => "zhihu/people",
+        }
+    }
+
+    pub fn canonical_url(&self) -> String {
+        match self {
+            Self::Answer { question: Some(question), id } => {
+                format!("https://www.zhihu.com/question/{}/answer/{}", question, id)
+            }
+            Self::Answer { question: None, id } => format!("https://www.zhihu.com/answer/{}", id),
+            Self::Question(id) => format!("https://www.zhihu.com/question/{}", id),
+            Self::Article(id) => format!("https://zhuanlan.zhihu.com/p/{}", id),
+            Self::Pin(id) => format!("https://www.zhihu.com/pin/{}", id),
+            Self::Column(slug) => format!("https://www.zhihu.com/column/{}", slug),
+            Self::People(slug) => format!("https://www.zhihu.com/people/{}", slug),
+        }
+    }
+
+    /// Identity of the entity, e.g. `zhihu/answer:123`; an answer is the same
+    /// whether or not its link names the question.
+    pub fn key(&self) -> String {
+        match self {
+            Self::Answer { id, .. } | Self::Question(id) | Self::Article(id) | Self::Pin(id) => {
+                format!("{}:{}", self.kind(), id)
+            }
+            Self::Column(slug) | Self::People(slug) => format!("{}:{}", self.kind(), slug.to_lowercase()),
+        }
+    }
+}
+
+pub fn canonicalize(url: &str) -> Option<Canonical> {
+    let entity = Entity::parse(url)?;
+    Some(Canonical {
+        url: entity.canonical_url(),
+        key: Some(entity.key()),
+        entity: Some(entity.kind()),
+        ..Default::default()
+    })
+}
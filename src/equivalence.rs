@@ -0,0 +1,123 @@
+use crate::settings::Settings;
+use crate::site;
+
+/// Groups of interchangeable URL prefixes, plus the scheme, `www.`, mobile
+/// subdomain and trailing-slash variants every URL has.
+///
+/// Prefixes are written without scheme or `www.`, e.g. `old.reddit.com` or
+/// `nitter.net`; the first prefix of a group is the canonical one.
+pub struct Equivalence {
+    groups: Vec<Vec<String>>,
+    mobile_subdomains: bool,
+}
+
+impl Equivalence {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            groups: settings
+                .equivalent_prefixes
+                .iter()
+                .filter(|group| !group.is_empty())
+                .cloned()
+                .collect(),
+            mobile_subdomains: settings.mobile_subdomains,
+        }
+    }
+
+    /// Single comparison key for `url`: no scheme, `www.` or trailing slash,
+    /// mobile subdomains dropped and group members mapped to the group's first
+    /// prefix. Two URLs are equivalent exactly when their keys are equal.
+    pub fn key(&self, url: &str) -> String {
+        let (host, rest) = split(url);
+        let host = if self.mobile_subdomains { strip_mobile(&host) } else { host };
+        let mut key = format!("{}{}", host, rest);
+        for group in &self.groups {
+            if let Some(member) = group.iter().find(|member| has_prefix(&key, member)) {
+                key = format!("{}{}", group[0], &key[member.len()..]);
+                break;
+            }
+        }
+        key.trim_end_matches('/').to_owned()
+    }
+}
+
+/// Splits `url` into a lowercased host without `www.` and the rest of the URL.
+fn split(url: &str) -> (String, String) {
+    let without_scheme = match url.find("://") {
+        Some(index) => &url[index + 3..],
+        None => url,
+    };
+    let end = without_scheme
+        .find(['/', '?', '#'])
+        .unwrap_or(without_scheme.len());
+    let host = without_scheme[..end].to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_owned();
+    (host, without_scheme[end..].to_owned())
+}
+
+/// Drops `m.` and `mobile.` labels in front of the registrable domain, so
+/// `en.m.wikipedia.org` becomes `en.wikipedia.org`.
+fn strip_mobile(host: &str) -> String {
+    let Some(domain) = site::registrable_domain(host) else {
+        return host.to_owned();
+    };
+    // the domain loses trailing dots, and a host that is not a valid
+    // domain name may not end with it at all
+    let Some(subdomain) = host.strip_suffix(domain.as_str()).and_then(|rest| rest.strip_suffix('.')) else {
+        return host.to_owned();
+    };
+    let labels: Vec<&str> = subdomain
+        .split('.')
+        .filter(|label| *label != "m" && *label != "mobile")
+        .collect();
+    if labels.is_empty() {
+        domain
+    } else {
+        format!("{}.{}", labels.join("."), domain)
+    }
+}
+
+/// Whether `key` starts with `prefix` followed by a path, query or the end.
+fn has_prefix(key: &str, prefix: &str) -> bool {
+    key.starts_with(prefix)
+        && (prefix.ends_with('/')
+            || matches!(key[prefix.len()..].chars().next(), None | Some('/' | '?' | '#')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheme_www_and_trailing_slashes_are_ignored() {
+        let equivalence = Equivalence::new(&Settings::default());
+        let key = equivalence.key("https://example.org/post");
+        assert_eq!(equivalence.key("http://www.example.org/post/"), key);
+        assert_eq!(equivalence.key("https://WWW.Example.org/post"), key);
+        assert_ne!(equivalence.key("https://example.org/post/2"), key);
+    }
+
+    #[test]
+    fn mobile_subdomains_are_dropped() {
+        let equivalence = Equivalence::new(&Settings::default());
+        assert_eq!(equivalence.key("https://en.m.wiktionary.org/wiki/rust"), "en.wiktionary.org/wiki/rust");
+        assert_eq!(equivalence.key("https://m.example.co.uk/"), "example.co.uk");
+        assert_eq!(equivalence.key("https://mm.example.org/"), "mm.example.org");
+    }
+
+    #[test]
+    fn group_members_map_to_the_first_prefix() {
+        let equivalence = Equivalence::new(&Settings::default());
+        assert_eq!(equivalence.key("https://old.reddit.com/r/rust/"), "reddit.com/r/rust");
+        assert_eq!(equivalence.key("https://nitter.net/rustlang"), "x.com/rustlang");
+        // a prefix only matches up to a path, query or the end
+        assert_eq!(equivalence.key("https://nitter.network/rustlang"), "nitter.network/rustlang");
+    }
+
+    #[test]
+    fn malformed_hosts_are_kept() {
+        let equivalence = Equivalence::new(&Settings::default());
+        assert_eq!(equivalence.key("https://中文%.com../x"), "中文%.com../x");
+        assert_eq!(equivalence.key("https://en.m.wiktionary.org./wiki"), "en.m.wiktionary.org./wiki");
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use regex::Regex;
+use serde::Deserialize;
+
+/// Markup of a vault file. Logseq graphs are Markdown by default but can
+/// keep pages and journals as Org files.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Format {
+    Markdown,
+    Org,
+}
+
+impl Format {
+    /// Format of a vault file, by its extension.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("org") => Self::Org,
+            _ => Self::Markdown,
+        }
+    }
+
+    /// Format new journal entries are written in: the configured one, else
+    /// the graph's `:preferred-format` from `logseq/config.edn`, else Markdown.
+    pub fn of_graph(root: &Path, configured: Option<Format>) -> Self {
+        if let Some(format) = configured {
+            return format;
+        }
+        let config = fs::read_to_string(root.join("logseq/config.edn")).unwrap_or_default();
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| Regex::new(r#"(?:^|[\s{,]):preferred-format\s+:?"?(\w+)"#).unwrap());
+        let preferred = config
+            .lines()
+            .filter(|line| !line.trim_start().starts_with(';'))
+            .find_map(|line| pattern.captures(line).map(|captures| captures[1].to_lowercase()));
+        match preferred.as_deref() {
+            Some("org") => Self::Org,
+            _ => Self::Markdown,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Org => "org",
+        }
+    }
+
+    /// Whether `line` starts a block: a `- ` bullet, or a `*` headline in Org.
+    pub fn is_block_start(self, line: &str) -> bool {
+        match self {
+            Self::Markdown => {
+                let line = line.trim_start();
+                line == "-" || line.starts_with("- ")
+            }
+            Self::Org => {
+                let stars = line.len() - line.trim_start_matches('*').len();
+                stars > 0 && matches!(line[stars..].chars().next(), None | Some(' '))
+            }
+        }
+    }
+
+    /// Outline depth of a block's first line: the width of the indentation
+    /// before the bullet in Markdown, with a tab as wide as four spaces, or
+    /// the number of stars in Org. `None` when `line` does not start a block.
+    pub fn depth(self, line: &str) -> Option<usize> {
+        if !self.is_block_start(line) {
+            return None;
+        }
+        let depth = match self {
+            Self::Markdown => line
+                .chars()
+                .take_while(|c| c.is_whitespace())
+                .map(|c| if c == '\t' { 4 } else { 1 })
+                .sum(),
+            Self::Org => line.len() - line.trim_start_matches('*').len(),
+        };
+        Some(depth)
+    }
+
+    /// Name and value of the property `line` sets, if any.
+    pub fn property_entry(self, line: &str) -> Option<(&str, &str)> {
+        let line = line.trim();
+        let (name, value) = match self {
+            Self::Markdown => line.split_once("::")?,
+            Self::Org => line.strip_prefix(':')?.split_once(':')?,
+        };
+        let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid || (self == Self::Org && matches!(name.to_uppercase().as_str(), "PROPERTIES" | "END")) {
+            return None;
+        }
+        Some((name, value.trim()))
+    }
+
+    /// Value of property `name` if `line` sets it: `name:: value` in
+    /// Markdown, `:name: value` inside a property drawer in Org.
+    pub fn property<'a>(self, line: &'a str, name: &str) -> Option<&'a str> {
+        let line = line.trim();
+        let value = match self {
+            Self::Markdown => line.strip_prefix(name)?.strip_prefix("::")?,
+            Self::Org => line.strip_prefix(':')?.strip_prefix(name)?.strip_prefix(':')?,
+        };
+        Some(value.trim())
+    }
+
+    /// Whether `block` already carries the tag `name`, compared as a whole
+    /// name and, like Logseq page names, regardless of case.
+    pub fn has_tag(self, block: &str, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.tags(block).iter().any(|tag| tag.to_lowercase() == name)
+    }
+
+    /// Names of the tags in `block`: `#name`, `#[[name]]` and `[[name]]`
+    /// anywhere, the `tags` property, and the headline tags in Org.
+    pub fn tags(self, block: &str) -> Vec<String> {
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| {
+            Regex::new(r"(?:^|\s)(?:#\[\[([^\]]+)\]\]|#([^\s#\[\],;]+)|\[\[([^\]]+)\]\])").unwrap()
+        });
+        let mut names: Vec<String> = Vec::new();
+        for line in block.lines() {
+            let inline = pattern
+                .captures_iter(line)
+                .filter_map(|captures| captures.get(1).or(captures.get(2)).or(captures.get(3)))
+                .map(|name| name.as_str().to_owned())
+                // Org links without a description
+                .filter(|name| !name.contains("://"));
+            let property = self
+                .property(line, "tags")
+                .into_iter()
+                .flat_map(|value| value.split(','))
+                .map(|name| tag_name(name).to_owned());
+            let headline = self
+                .headline_tags(line)
+                .into_iter()
+                .flat_map(|tags| tags.split(':'))
+                .map(str::to_owned);
+            for name in inline.chain(property).chain(headline) {
+                if !name.is_empty() && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// `line` with `text` added at the end of its content, which in Org is
+    /// before the headline's `:tag:` list.
+    pub fn append(self, line: &str, text: &str) -> String {
+        match self.headline_tags(line) {
+            Some(tags) => {
+                let content = line.trim_end();
+                let content = content[..content.len() - tags.len()].trim_end();
+                format!("{} {} {}", content, text.trim_end(), tags)
+            }
+            None => format!("{} {}", line, text),
+        }
+    }
+
+    /// The trailing `:a:b:` tag list of an Org headline.
+    fn headline_tags(self, line: &str) -> Option<&str> {
+        if self != Self::Org || !self.is_block_start(line) {
+            return None;
+        }
+        line.trim_end()
+            .rsplit(' ')
+            .next()
+            .filter(|tags| tags.len() > 2 && tags.starts_with(':') && tags.ends_with(':') && !tags.contains("::"))
+    }
+
+    /// Journal entry for a saved link, with the card marker `;;` and the
+    /// properties of the link below it.
+    pub fn entry(self, title: &str, site: &str, url: &str, tags: &[String], properties: &[(String, String)]) -> String {
+        let tags: String = tags.iter().map(|tag| format!("{} ", tag)).collect();
+        match self {
+            Self::Markdown => {
+                let mut entry = format!("\n- {}-[{}]({}) {};; ", title, site, url, tags);
+                for (name, value) in properties {
+                    entry += &format!("\n  {}:: {}", name, value);
+                }
+                entry
+            }
+            Self::Org => {
+                let mut entry = format!("\n* {}-[[{}][{}]] {};; ", title, url, site, tags);
+                if !properties.is_empty() {
+                    entry += "\n:PROPERTIES:";
+                    for (name, value) in properties {
+                        entry += &format!("\n:{}: {}", name, value);
+                    }
+                    entry += "\n:END:";
+                }
+                entry
+            }
+        }
+    }
+}
+
+/// How tags are written: `#[[name]]`, `#name` or `[[name]]`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TagStyle {
+    Bracketed,
+    /// `#name`, or `#[[name]]` when the name has characters `#` cannot take.
+    Hash,
+    PageRef,
+}
+
+impl TagStyle {
+    pub fn render(self, name: &str) -> String {
+        match self {
+            Self::Hash if !name.contains(|c: char| c.is_whitespace() || "#[],;".contains(c)) => format!("#{}", name),
+            Self::Bracketed | Self::Hash => format!("#[[{}]]", name),
+            Self::PageRef => format!("[[{}]]", name),
+        }
+    }
+}
+
+/// Where the tags of a saved link go.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TagPlacement {
+    /// After the link on the block's first line.
+    Inline,
+    /// In the block's `tags::` property.
+    Property,
+}
+
+/// The name of a tag written in any `TagStyle`, or bare.
+pub fn tag_name(tag: &str) -> &str {
+    let tag = tag.trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+    tag.strip_prefix("[[")
+        .and_then(|tag| tag.strip_suffix("]]"))
+        .unwrap_or(tag)
+        .trim()
+}
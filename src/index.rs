@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use crate::canonical;
+use crate::equivalence::Equivalence;
+use crate::format::Format;
+use crate::links;
+use crate::normalize::Normalizer;
+use crate::scope::{self, Scope};
+use crate::search;
+use crate::settings::Settings;
+use crate::titles;
+
+/// Bumped whenever the stored format or the way links are keyed changes.
+const VERSION: u32 = 4;
+
+/// A place in the vault where a link is saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    /// Path relative to the vault folder.
+    pub file: PathBuf,
+    /// 1-based line number of the first line of the block holding the link,
+    /// where tags for the entry go.
+    pub line_number: usize,
+    pub line: String,
+    /// The whole block around the line: its bullet plus continuation lines.
+    pub block: String,
+    /// Title of the block, for near-duplicate checks.
+    pub title: String,
+    /// The block's `id` property, which block references point to.
+    pub id: Option<String>,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line_number, self.block)
+    }
+}
+
+/// What is known about one vault file when it was last indexed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileEntry {
+    /// Modification time in nanoseconds since the epoch.
+    modified: u128,
+    len: u64,
+    /// SHA-256 of the contents, so a touched but unchanged file is not re-parsed.
+    hash: String,
+    links: Vec<(String, Location)>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Stored {
+    version: u32,
+    root: PathBuf,
+    /// Settings that affect keys; the index is rebuilt when they change.
+    fingerprint: String,
+    files: BTreeMap<PathBuf, FileEntry>,
+}
+
+/// On-disk index of the links saved in the vault, keyed by the same identity
+/// new links are looked up by, so a check does not scan the vault.
+pub struct Index {
+    path: PathBuf,
+    stored: Stored,
+    locations: HashMap<String, Vec<Location>>,
+    conflict_copies: Vec<PathBuf>,
+    scope: Scope,
+    normalizer: Normalizer,
+    equivalence: Equivalence,
+}
+
+impl Index {
+    /// Loads the index at `path` and brings it up to date with the vault,
+    /// re-reading only files whose size or modification time changed. A
+    /// missing, unreadable or outdated index is rebuilt from scratch.
+    pub fn open(path: &str, root: &str, settings: &Settings) -> Result<Self, Error> {
+        let fingerprint = fingerprint(settings);
+        let stored = match fs::read_to_string(path) {
+            Ok(str) => serde_json::from_str(&str).unwrap_or_else(|e| {
+                println!("Rebuilding unreadable index {}: {}", path, e);
+                Stored::default()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Stored::default(),
+            Err(e) => return Err(e),
+        };
+        let stored = if stored.version == VERSION && stored.root == Path::new(root) && stored.fingerprint == fingerprint {
+            stored
+        } else {
+            Stored {
+                version: VERSION,
+                root: PathBuf::from(root),
+                fingerprint,
+                files: BTreeMap::new(),
+            }
+        };
+        let mut index = Self {
+            path: PathBuf::from(path),
+            stored,
+            locations: HashMap::new(),
+            conflict_copies: Vec::new(),
+            scope: Scope::new(settings).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?,
+            normalizer: Normalizer::new(settings),
+            equivalence: Equivalence::new(settings),
+        };
+        index.update()?;
+        Ok(index)
+    }
+
+    /// Sync conflict copies found in scope. They are not indexed, so links
+    /// in them are neither found nor rewritten.
+    pub fn conflict_copies(&self) -> &[PathBuf] {
+        &self.conflict_copies
+    }
+
+    /// Identity `url` is indexed under: the site rule's key when there is
+    /// one, otherwise the equivalence key of the normalized URL.
+    pub fn key(&self, url: &str) -> String {
+        let url = self.normalizer.normalize(url);
+        match canonical::canonicalize(&url) {
+            Some(canonical) => canonical
+                .key
+                .unwrap_or_else(|| self.equivalence.key(&canonical.url)),
+            None => self.equivalence.key(&url),
+        }
+    }
+
+    /// Every place a link with one of `keys` is saved, without duplicates.
+    pub fn find(&self, keys: &[String]) -> Vec<Location> {
+        let mut found: Vec<Location> = Vec::new();
+        for location in keys.iter().filter_map(|key| self.locations.get(key)).flatten() {
+            if !found
+                .iter()
+                .any(|f| f.file == location.file && f.line_number == location.line_number)
+            {
+                found.push(location.clone());
+            }
+        }
+        found
+    }
+
+    /// Saved blocks whose title is at least `threshold` similar to `title`,
+    /// most similar first.
+    pub fn similar(&self, title: &str, threshold: f64) -> Vec<(f64, Location)> {
+        let shingles = titles::shingles(title);
+        let mut similar: Vec<(f64, Location)> = Vec::new();
+        for (_, location) in self.stored.files.values().flat_map(|entry| &entry.links) {
+            if similar
+                .iter()
+                .any(|(_, s)| s.file == location.file && s.line_number == location.line_number)
+            {
+                continue;
+            }
+            let score = titles::similarity(&shingles, &titles::shingles(&location.title));
+            if score >= threshold {
+                similar.push((score, location.clone()));
+            }
+        }
+        similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+        similar
+    }
+
+    /// Re-indexes one file after it was rewritten to `contents`, so later
+    /// lookups see the edit even when it was not written to disk.
+    pub fn refresh(&mut self, file: &Path, contents: &str) -> Result<(), Error> {
+        self.remove_locations(file);
+        let metadata = fs::metadata(self.stored.root.join(file))?;
+        self.index_contents(file, &metadata, contents.as_bytes());
+        self.add_locations(file);
+        Ok(())
+    }
+
+    /// Writes the index next to a temporary copy first, so an interrupted
+    /// run leaves the previous index intact.
+    pub fn save(&self) -> Result<(), Error> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let temp_file = NamedTempFile::new_in(dir)?;
+        serde_json::to_writer(&temp_file, &self.stored)?;
+        temp_file.persist(&self.path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    fn update(&mut self) -> Result<(), Error> {
+        let root = self.stored.root.clone();
+        let mut present = HashSet::new();
+        for path in search::vault_files(&root)? {
+            let relative = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+            if !self.scope.contains(&relative) {
+                continue;
+            }
+            if scope::is_conflict_copy(&relative) {
+                self.conflict_copies.push(relative);
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            let unchanged = self.stored.files.get(&relative).is_some_and(|entry| {
+                entry.len == metadata.len() && entry.modified == modified(&metadata)
+            });
+            if !unchanged {
+                self.index_file(&relative)?;
+            }
+            present.insert(relative);
+        }
+        self.stored.files.retain(|file, _| present.contains(file));
+        self.locations.clear();
+        let files: Vec<PathBuf> = self.stored.files.keys().cloned().collect();
+        for file in files {
+            self.add_locations(&file);
+        }
+        Ok(())
+    }
+
+    fn index_file(&mut self, file: &Path) -> Result<(), Error> {
+        let path = self.stored.root.join(file);
+        let metadata = fs::metadata(&path)?;
+        let bytes = fs::read(&path)?;
+        self.index_contents(file, &metadata, &bytes);
+        Ok(())
+    }
+
+    fn index_contents(&mut self, file: &Path, metadata: &fs::Metadata, bytes: &[u8]) {
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        if let Some(entry) = self.stored.files.get_mut(file) {
+            if entry.hash == hash {
+                entry.modified = modified(metadata);
+                entry.len = metadata.len();
+                return;
+            }
+        }
+        // binary files are kept with no links so they are not read again
+        let links = if bytes.contains(&0) {
+            Vec::new()
+        } else {
+            self.extract(file, &String::from_utf8_lossy(bytes))
+        };
+        self.stored.files.insert(
+            file.to_path_buf(),
+            FileEntry {
+                modified: modified(metadata),
+                len: metadata.len(),
+                hash,
+                links,
+            },
+        );
+    }
+
+    /// Keys and locations of the links in `contents`. Besides URLs, the
+    /// `source::` property written for papers and repositories is a key itself.
+    fn extract(&self, file: &Path, contents: &str) -> Vec<(String, Location)> {
+        let format = Format::of(file);
+        let lines: Vec<&str> = contents.lines().collect();
+        let urls = links::extract(contents, format)
+            .into_iter()
+            .map(|link| (link.line, self.key(&link.url)));
+        let sources = lines.iter().enumerate().filter_map(|(index, line)| {
+            let id = format.property(line, "source")?.split_whitespace().next()?;
+            Some((index, id.to_owned()))
+        });
+        let mut entries: Vec<(String, Location)> = Vec::new();
+        for (index, key) in urls.chain(sources) {
+            let (start, block) = block(&lines, index, format);
+            if entries.iter().any(|(k, location)| *k == key && location.line_number == start + 1) {
+                continue;
+            }
+            let id = block.lines().find_map(|line| format.property(line, "id")).map(str::to_owned);
+            let location = Location {
+                file: file.to_path_buf(),
+                line_number: start + 1,
+                line: lines[start].to_string(),
+                title: titles::from_block(lines[start]),
+                block,
+                id,
+            };
+            entries.push((key, location));
+        }
+        entries
+    }
+
+    fn add_locations(&mut self, file: &Path) {
+        if let Some(entry) = self.stored.files.get(file) {
+            for (key, location) in &entry.links {
+                self.locations.entry(key.clone()).or_default().push(location.clone());
+            }
+        }
+    }
+
+    fn remove_locations(&mut self, file: &Path) {
+        if let Some(entry) = self.stored.files.get(file) {
+            for (key, _) in &entry.links {
+                if let Some(locations) = self.locations.get_mut(key) {
+                    locations.retain(|location| location.file != file);
+                }
+            }
+        }
+    }
+}
+
+fn modified(metadata: &fs::Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos())
+}
+
+fn fingerprint(settings: &Settings) -> String {
+    let relevant = format!(
+        "{:?} {:?} {:?} {:?}",
+        settings.equivalent_prefixes, settings.mobile_subdomains, settings.fragment_policy, settings.spa_hosts
+    );
+    format!("{:x}", Sha256::digest(relevant.as_bytes()))
+}
+
+/// The first line and text of the block containing line `index`: back to
+/// its bullet or headline and on over continuation lines, or the paragraph
+/// around it outside of outlines.
+fn block(lines: &[&str], index: usize, format: Format) -> (usize, String) {
+    let mut start = index;
+    while !format.is_block_start(lines[start]) && start > 0 && !lines[start - 1].trim().is_empty() {
+        start -= 1;
+    }
+    let mut end = index + 1;
+    while end < lines.len() && !format.is_block_start(lines[end]) && !lines[end].trim().is_empty() {
+        end += 1;
+    }
+    (start, lines[start..end].join("\n"))
+}
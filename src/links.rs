@@ -0,0 +1,175 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::format::Format;
+
+/// A link found in a Markdown or Org page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// The URL as written, without surrounding `<>` or link syntax.
+    pub url: String,
+    /// 0-based index of the line the link is on.
+    pub line: usize,
+}
+
+struct Patterns {
+    inline_code: Regex,
+    /// Patterns whose first group is a URL, tried in this order. Each match
+    /// is blanked out so a later pattern does not find the URL again.
+    markdown: Vec<Regex>,
+    org: Vec<Regex>,
+    org_block: Regex,
+    bare: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let url_with_parens = r"https?://[^\s<>()]+(?:\([^\s<>()]*\)[^\s<>()]*)*";
+        Patterns {
+            inline_code: Regex::new(r"`[^`]*`").unwrap(),
+            markdown: vec![
+                // `[id]: url "title"` reference definitions
+                Regex::new(r#"^\s*(?:[-*+]\s+)?\[[^\]]+\]:\s*<?(https?://[^\s>]+)>?"#).unwrap(),
+                // `[title](url "title")` and `![alt](url)`
+                Regex::new(&format!(r#"\]\(\s*<?({})>?(?:\s+"[^"]*")?\s*\)"#, url_with_parens)).unwrap(),
+                // `<a href="url">`
+                Regex::new(r#"(?i)<a\s[^>]*?href\s*=\s*["'](https?://[^"']+)["']"#).unwrap(),
+                // Logseq embeds such as `{{video url}}`
+                Regex::new(r"\{\{\s*(?:video|youtube|tweet|twitter|bilibili|vimeo|embed)\s+(https?://[^\s}]+)\s*\}\}").unwrap(),
+                // `<url>` autolinks
+                Regex::new(r"<(https?://[^\s<>]+)>").unwrap(),
+            ],
+            org: vec![
+                // `[[url][title]]` and `[[url]]`
+                Regex::new(r"\[\[(https?://[^\]]+)\](?:\[[^\]]*\])?\]").unwrap(),
+            ],
+            org_block: Regex::new(r"(?i)^\s*#\+(begin|end)_(src|example)\b").unwrap(),
+            bare: Regex::new(r#"https?://[^\s<>"'\[\]()`{}]+(?:\([^\s<>"'\[\]()`{}]*\)[^\s<>"'\[\]()`{}]*)*"#).unwrap(),
+        }
+    })
+}
+
+/// Every link in `contents`. In Markdown: inline, reference-style and HTML
+/// links, autolinks, Logseq embeds and bare URLs, including the values of
+/// properties such as `url::`; fenced code blocks and inline code are
+/// skipped. In Org: `[[url][title]]` links and bare URLs outside of
+/// source and example blocks.
+pub fn extract(contents: &str, format: Format) -> Vec<Link> {
+    let patterns = patterns();
+    let mut links = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut in_org_block = false;
+    for (index, line) in contents.lines().enumerate() {
+        if format == Format::Org {
+            if let Some(captures) = patterns.org_block.captures(line) {
+                in_org_block = captures[1].eq_ignore_ascii_case("begin");
+                continue;
+            }
+            if in_org_block {
+                continue;
+            }
+            let mut text = line.to_owned();
+            let urls = syntax_urls(&mut text, &patterns.org);
+            push_links(&mut links, index, urls.into_iter().chain(bare_urls(&text)));
+            continue;
+        }
+
+        // Logseq writes fences inside bullets, e.g. "- ```rust"
+        let content = line.trim_start();
+        let content = content.strip_prefix("- ").unwrap_or(content).trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|marker| content.starts_with(marker));
+        match (fence, marker) {
+            (Some(open), Some(marker)) if open == marker => {
+                fence = None;
+                continue;
+            }
+            (Some(_), _) => continue,
+            (None, Some(marker)) => {
+                fence = Some(marker);
+                continue;
+            }
+            (None, None) => {}
+        }
+
+        let mut text = line.to_owned();
+        blank(&mut text, &patterns.inline_code);
+        let urls = syntax_urls(&mut text, &patterns.markdown);
+        push_links(&mut links, index, urls.into_iter().chain(bare_urls(&text)));
+    }
+    links
+}
+
+/// URLs matched by `syntax`, blanking each match out of `text`.
+fn syntax_urls(text: &mut String, syntax: &[Regex]) -> Vec<String> {
+    let mut urls = Vec::new();
+    for pattern in syntax {
+        let found: Vec<String> = pattern
+            .captures_iter(text)
+            .map(|captures| captures[1].to_owned())
+            .collect();
+        blank(text, pattern);
+        urls.extend(found);
+    }
+    urls
+}
+
+fn bare_urls(text: &str) -> Vec<String> {
+    patterns()
+        .bare
+        .find_iter(text)
+        // punctuation and emphasis right after a bare URL end the sentence
+        .map(|url| url.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '_', '~']).to_owned())
+        .collect()
+}
+
+fn push_links(links: &mut Vec<Link>, line: usize, urls: impl Iterator<Item = String>) {
+    for url in urls {
+        if !links.iter().any(|link| link.line == line && link.url == url) {
+            links.push(Link { url, line });
+        }
+    }
+}
+
+/// Replaces every match of `pattern` in `text` with spaces of the same length.
+fn blank(text: &mut String, pattern: &Regex) {
+    let ranges: Vec<_> = pattern.find_iter(text).map(|found| found.range()).collect();
+    for range in ranges {
+        text.replace_range(range.clone(), &" ".repeat(range.len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(contents: &str) -> Vec<String> {
+        extract(contents, Format::Markdown).into_iter().map(|link| link.url).collect()
+    }
+
+    #[test]
+    fn markdown_and_logseq_syntax() {
+        let page = "- [Rust](https://www.rust-lang.org/ \"Rust\") and <https://docs.rs>\n\
+                    - {{video https://www.youtube.com/watch?v=dQw4w9WgXcQ}}\n  url:: https://crates.io/crates/regex\n\
+                    - see https://en.wikipedia.org/wiki/Rust_(programming_language).\n";
+        assert_eq!(
+            urls(page),
+            [
+                "https://www.rust-lang.org/",
+                "https://docs.rs",
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://crates.io/crates/regex",
+                "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+            ]
+        );
+        assert_eq!(extract(page, Format::Markdown)[3].line, 2);
+    }
+
+    #[test]
+    fn code_is_skipped() {
+        let page = "- run `curl https://example.org/inline`\n\
+                    - ```sh\n  curl https://example.org/fenced\n  ```\n\
+                    ~~~\nhttps://example.org/tilde ``` still fenced\n~~~\n\
+                    - after https://example.org/after\n";
+        assert_eq!(urls(page), ["https://example.org/after"]);
+    }
+}
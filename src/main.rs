@@ -1,20 +1,29 @@
+mod batch;
+mod canonical;
+mod equivalence;
+mod format;
+mod index;
+mod links;
+mod normalize;
+mod outline;
+mod report;
+mod rewrite;
+mod scope;
+mod search;
+mod settings;
+mod site;
+mod titles;
+mod undo;
 mod util;
 
 use std::env;
 use std::fs::File;
-use std::io::{prelude::*, BufReader, BufWriter, ErrorKind};
-use std::path::PathBuf;
+use std::io::BufReader;
 use reqwest::{Client, Method, Url};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::process::Command;
-use std::io::Error;
-use regex::Regex;
-use tempfile::NamedTempFile;
-use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use serde_json::from_reader;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,12 +33,6 @@ struct Config {
     code: String,
 }
 
-#[derive(Deserialize)]
-struct UrlEntry {
-    name: String,
-    url: String,
-}
-
 #[derive(Deserialize)]
 struct UrlList {
     metadata: Metadata,
@@ -45,7 +48,9 @@ struct Metadata {
 struct ListItem {
     name: String,
     url: String,
+    #[allow(dead_code)]
     extra_prefix: String,
+    #[allow(dead_code)]
     extra_suffix: String,
     tags: Vec<String>,  // 新增tags字段
 }
@@ -77,38 +82,67 @@ struct PocketAction {
     time: String,
 }
 
-struct UrlTransformation {
-    prefix: String,
-    replacements: Vec<String>,
-}
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args: Vec<String> = env::args().collect();
+    // a dry run goes through every item but leaves the vault, the index
+    // and Pocket untouched, and shows what it would have changed instead;
+    // fetching the Pocket list is the only request it makes
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    args.retain(|arg| arg != "--dry-run");
+    if args.get(1).map(String::as_str) == Some("check-rules") {
+        let failures = canonical::check_cases();
+        for failure in &failures {
+            println!("{}", failure);
+        }
+        if !failures.is_empty() {
+            return Err(format!("{} canonicalization cases failed", failures.len()).into());
+        }
+        println!("All canonicalization cases passed");
+        return Ok(());
+    }
 
-impl UrlTransformation {
-    fn new(prefix: &str, replacements: &[&str]) -> Self {
-        Self {
-            prefix: prefix.to_string(),
-            replacements: replacements.iter().map(|s| s.to_string()).collect(),
+    dotenv::dotenv().ok();
+    if args.get(1).map(String::as_str) == Some("search") {
+        let settings = settings::load()?;
+        let scope = scope::Scope::new(&settings)?;
+        let vault = search::Vault::load(&env::var("folderPath")?, settings.match_mode, &scope)?;
+        for found in vault.search(&args[2..].join(" ")) {
+            println!("{}", found);
         }
+        return Ok(());
     }
 
-    fn apply(&self, url: &str, url_alternatives: &mut HashSet<String>) {
-        if url.starts_with(&self.prefix) {
-            for replacement in &self.replacements {
-                let new_url = url.replace(&self.prefix, replacement);
-                url_alternatives.insert(new_url);
+    if args.get(1).map(String::as_str) == Some("undo") {
+        let settings = settings::load()?;
+        let log = PathBuf::from(settings.undo_log_path.as_deref().ok_or("No undo log, undoLogPath is null")?);
+        let Some(run) = args.get(2) else {
+            for (run, files) in undo::runs(&log)? {
+                println!("{} changed {} file(s)", run, files.len());
             }
+            return Ok(());
+        };
+        let folder_path = env::var("folderPath")?;
+        let mut rewriter = if dry_run {
+            rewrite::Rewriter::dry_run(Path::new(&folder_path))
+        } else {
+            rewrite::Rewriter::new(Path::new(&folder_path), &settings)
+        };
+        for (file, outcome) in undo::undo(&log, run, &mut rewriter)? {
+            println!("{}: {}", file.display(), outcome);
+        }
+        print!("{}", rewriter);
+        if let Some(undo_run) = rewriter.logged_run() {
+            println!("Logged the undo itself as run {}", undo_run);
         }
+        return Ok(());
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv::dotenv().ok();
     let consumer_key = env::var("consumerKey")?;
     let folder_path = env::var("folderPath")?;
+    let settings = settings::load()?;
+    let normalizer = normalize::Normalizer::new(&settings);
 
-    let mut code = String::new();
-    let mut access_token = String::new();
-    let mut user_name = String::new();
     let mut action: Vec<PocketAction> = Vec::new();
 
     let config_str = std::fs::read_to_string("config.json");
@@ -125,26 +159,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 code,
             }
         }
+        Err(_) if dry_run => {
+            return Err("A dry run needs config.json, run once without --dry-run to authorize".into());
+        }
         Err(_) => {
-            let (c, a, u) = get_code(&consumer_key).await?;
-            code = c;
-            access_token = a;
-            user_name = u;
+            let (code, access_token, user_name) = get_code(&consumer_key).await?;
             Config {
-                access_token: access_token.clone(),
-                user_name: user_name.clone(),
-                code: code.clone(),
+                access_token,
+                user_name,
+                code,
             }
         }
     };
 
-    access_token = config.access_token.clone();
-    user_name = config.user_name.clone();
-    code = config.code.clone();
+    let access_token = config.access_token.clone();
 
     let client = Client::new();
 
-    let args: Vec<String> = env::args().collect();
     let mut pocket_list = PocketList { list: HashMap::new() };
     let mut is_data_input_from_pocket = true;
     if args.len() > 1 {
@@ -167,18 +198,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             for (i, tag_content) in item.tags.iter().enumerate() {
                 let clean_tag = tag_content.trim();
                 if !clean_tag.is_empty() {
-                    // 自动添加标签格式
-                    let formatted_tag = if clean_tag.starts_with("#[[") {
-                        clean_tag.to_string()
-                    } else {
-                        format!("#[[{}]]", clean_tag)
-                    };
-                    
                     tags.insert(
                         i.to_string(),
                         Tag {
                             item_id: i.to_string(),
-                            tag: formatted_tag,
+                            tag: clean_tag.to_string(),
                         },
                     );
                 }
@@ -223,78 +247,78 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("Empty, nothing to parse");
         return Ok(());
     }
+    let mut index = index::Index::open(&settings.index_path, &folder_path, &settings)?;
+    for file in index.conflict_copies() {
+        println!("Sync conflict copy, left untouched: {}", file.display());
+    }
+    let journal_format = format::Format::of_graph(Path::new(&folder_path), settings.journal_format);
+    let mut rewriter = if dry_run {
+        rewrite::Rewriter::dry_run(Path::new(&folder_path))
+    } else {
+        rewrite::Rewriter::new(Path::new(&folder_path), &settings)
+    };
+    rewriter.check_lock()?;
+    let mut report = report::Report::default();
+    let mut batch = batch::Batch::default();
     for (key, item) in pocket_list.list {
         let mut url = item.given_url;
         if url.is_empty() {
             url = item.resolved_url.unwrap_or_default();
         }
-        let site = url
-            .replace("https://", "")
-            .replace("http://", "")
-            .replace(|c: char| c == '/' || c == ':', "")
-            .trim_start_matches("www.")
-            .trim_end_matches(".com")
-            // only keep the first part of the domain
-            .split('.')
-            .next()
-            .unwrap_or_default()
-            .to_owned();
-        let mut reg = regex::Regex::new(r"https://www.zhihu.com/question/\d+/answer/").unwrap();
-        if reg.is_match(&url) {
-            let replace = regex::Regex::new(r"\?\S+$").unwrap();
-            url = replace.replace_all(&url, "").to_owned().to_string();
-        }
-        reg = regex::Regex::new(r"https://www.zhihu.com/people/").unwrap();
-        if reg.is_match(&url) {
-            let replace = regex::Regex::new(r"\?\S+$").unwrap();
-            url = replace.replace_all(&url, "").to_owned().to_string();
-        }
-        let pattern = regex::Regex::new(r"\?.*").unwrap();
-        if url.starts_with("https://twitter.com/") || url.starts_with("https://x.com") {
-            url = pattern.replace(&url, "").to_owned().to_string();
-            // Replace x.com with twitter.com
-            url = url.replace("twitter.com", "x.com");
-            // appending /with_replies to Twitter URLs
-            let twitter_pattern = regex::Regex::new(r"^(https://x\.com/[a-zA-Z0-9_]+/?$)").unwrap();
-            if twitter_pattern.is_match(&url) {
-                // to lower case first
-                url = url.to_lowercase();
-                url = if url.ends_with("/") { url + "with_replies" } else { url + "/with_replies" };
+        // site rules still get the fragment the fragment policy may drop,
+        // such as the timestamp in a YouTube `#t=90`
+        let fragment = Url::parse(&url).ok().and_then(|parsed| parsed.fragment().map(str::to_owned));
+        url = normalizer.normalize(&url);
+        let rule_input = match fragment {
+            Some(fragment) if !fragment.is_empty() && !url.contains('#') => format!("{}#{}", url, fragment),
+            _ => url.clone(),
+        };
+        // a share link is still looked up as itself after it is resolved
+        let mut keys = vec![index.key(&url)];
+        let mut properties: Vec<(String, String)> = Vec::new();
+        let mut canonical_tags: Vec<String> = Vec::new();
+        // share links stay unresolved in a dry run, which makes no requests
+        let canonical = if dry_run {
+            canonical::canonicalize(&rule_input)
+        } else {
+            canonical::canonicalize_resolved(&client, &rule_input).await
+        };
+        if let Some(canonical) = canonical {
+            url = if canonical.key.is_none() && canonical.entity.is_none() {
+                // a resolved share link no rule knows
+                normalizer.normalize(&canonical.url)
+            } else {
+                canonical.url
+            };
+            properties = canonical.properties;
+            canonical_tags = canonical.tags;
+            if let Some(id) = canonical.id {
+                properties.push(("source".to_owned(), id));
+            }
+            if let Some(tag) = canonical.entity.and_then(|entity| settings.entity_tags.get(entity)) {
+                canonical_tags.push(tag.clone());
             }
         }
 
-        if url.contains("m.youtube.com") {
-            url = url.replace("m.youtube.com", "youtube.com");
-        }
-
+        // tags are kept by name and written in the configured style
         let mut tags: Vec<String> = Vec::new();
-        if let Some(item_tags) = item.tags {
-            for tag in item_tags {
-                let ignore_case_tag = tag.1.tag;
-                tags.push(ignore_case_tag);
+        let item_tags = item.tags.into_iter().flat_map(HashMap::into_values).map(|tag| tag.tag);
+        for tag in item_tags.chain(canonical_tags) {
+            let name = format::tag_name(&tag).to_owned();
+            if !name.is_empty() && !tags.contains(&name) {
+                tags.push(name);
             }
         }
-        if url.contains("youtube.com") || url.contains("bilibili.com") || url.contains("douyin.com") {
-            tags.push("#[[vquest]]".to_string());
+        let vquest = "vquest".to_string();
+        if (url.contains("youtube.com") || url.contains("bilibili.com") || url.contains("douyin.com")) && !tags.contains(&vquest) {
+            tags.push(vquest);
         }
         if tags.is_empty() {
-            tags.push("#[[c]]".to_owned());
-        }
-        // Iterate and ensure each tag starts with '#'
-        for tag in tags.iter_mut() {
-            // Trim leading and trailing whitespace
-            let trimmed_tag = tag.trim();
-            
-            if !trimmed_tag.starts_with('#') {
-                *tag = format!("#[[{}]]", trimmed_tag);
-            } else {
-                *tag = trimmed_tag.to_string(); // Update the tag to the trimmed version if it already starts with '#'
-            }
-        }
-        if url.contains("m.youtube.com") {
-            url = url.replace("m.youtube.com", "youtube.com");
+            tags.push(batch::UNTAGGED.to_owned());
         }
 
+        // from the final URL, so every form of a link gets the same label
+        let site = site::label(&url, &settings.site_aliases);
 
         let mut title = item
             .resolved_title
@@ -305,79 +329,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         title = title.replace("#", "");
         
-        let mut res: Result<(), Error> = Err(Error::new(ErrorKind::Other, "Failed to execute command"));
-        if true {
-            let mut url_alternatives = HashSet::new();
-            url_alternatives.insert(url.to_owned());
-
-            // if url.starts_with("https://m.weibo.cn/") {
-            //     let new_url1 = url.replace("https://m.weibo.cn/", "https://weibo.cn/");
-            //     let new_url2 = url.replace("https://m.weibo.cn/", "https://weibo.com/");
-            //     url_alternatives.insert(new_url1);
-            //     url_alternatives.insert(new_url2);
-            // } else if url.starts_with("https://weibo.cn/") {
-            //     let new_url1 = url.replace("https://weibo.cn/", "https://m.weibo.cn/");
-            //     let new_url2 = url.replace("https://weibo.cn/", "https://weibo.com/");
-            //     url_alternatives.insert(new_url1);
-            //     url_alternatives.insert(new_url2);
-            // } else if url.starts_with("https://weibo.com/") {
-            //     let new_url1 = url.replace("https://weibo.com/", "https://weibo.cn/");
-            //     let new_url2 = url.replace("https://weibo.com/", "https://m.weibo.cn/");
-            //     url_alternatives.insert(new_url1);
-            //     url_alternatives.insert(new_url2);
-            // }
-            let url_transformations = vec![
-                UrlTransformation::new("https://m.weibo.cn/", &["https://weibo.cn/", "https://weibo.com/"]),
-                UrlTransformation::new("https://weibo.cn/", &["https://m.weibo.cn/", "https://weibo.com/"]),
-                UrlTransformation::new("https://weibo.com/", &["https://weibo.cn/", "https://m.weibo.cn/"]),
-                UrlTransformation::new("https://www.m.weibo.cn/", &["https://weibo.cn/", "https://weibo.com/", "https://m.weibo.cn/"]),
-                UrlTransformation::new("https://www.weibo.cn/", &["https://m.weibo.cn/", "https://weibo.com/", "https://weibo.cn/"]),
-                UrlTransformation::new("https://www.weibo.com/", &["https://weibo.cn/", "https://m.weibo.cn/", "https://weibo.com/"]),
-            ];
-
-            // Apply the transformation rules
-            for transformation in &url_transformations {
-                transformation.apply(&url, &mut url_alternatives);
-            }
-
-
-            for url in url_alternatives.clone().iter() {
-                if !url.ends_with('/') {
-                    let slash_url = url.to_owned() + "/";
-                    url_alternatives.insert(slash_url);
-                }
-            }
-            // reversion
-            for url in url_alternatives.clone().iter() {
-                if url.ends_with('/') {
-                    let slash_url = url.trim_end_matches('/').to_owned();
-                    url_alternatives.insert(slash_url);
-                }
-            }
-
-            if is_data_input_from_pocket {
-                for alternative_url in url_alternatives {
-                    if let Ok(_) = util::check(&folder_path, &alternative_url, &tags) {
-                        res = Ok(());
-                        break;
-                    }
-                }
-            } else {
-                for alternative_url in url_alternatives {
-                    if let Ok(_) = util::check_and_reset(&folder_path, &alternative_url, &tags) {
-                        res = Ok(());
-                        break;
-                    }
-                }
-            }
+        let canonical_key = index.key(&url);
+        if !keys.contains(&canonical_key) {
+            keys.push(canonical_key);
         }
+        let res = if is_data_input_from_pocket {
+            util::check(&index, &keys, &tags)
+        } else {
+            util::check_and_reset(&mut index, &mut rewriter, &keys, &tags, &settings, &mut report.untagged)
+        };
         
-        if res.is_err() {
-            let tags_string = tags.iter().map(|tag| format!("{}", tag)).collect::<Vec<String>>().join(" ");
-            output += &format!(
-                "\n- {}-[{}]({}) {} ;; ",
-                title, site, url, tags_string
-            );
+        match res {
+            Ok(locations) => report.duplicates.push(report::Duplicate {
+                title,
+                url,
+                found: locations.into_iter().map(report::Found::new).collect(),
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let near = settings
+                    .near_duplicate_threshold
+                    .and_then(|threshold| index.similar(&title, threshold).into_iter().next());
+                if let Some((similarity, location)) = near {
+                    properties.push(("near-duplicate".to_owned(), report::block_link(&location)));
+                    report.near_duplicates.push(report::NearDuplicate {
+                        title: title.clone(),
+                        url: url.clone(),
+                        similarity,
+                        found: report::Found::new(location),
+                    });
+                }
+                batch.add(batch::Entry::new(keys, title, site, url, tags, properties));
+            }
+            Err(e) => return Err(e.into()),
         }
 
         let archive = PocketAction {
@@ -388,18 +371,58 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         action.push(archive);
     }
 
+    if !dry_run {
+        index.save()?;
+    }
+    report.conflicts = rewriter.conflicts().to_vec();
+    report.unreadable = rewriter.unreadable().to_vec();
+
+    for entry in batch.entries {
+        if entry.is_merged() {
+            report.merges.push(report::Merge {
+                title: entry.title.clone(),
+                url: entry.url.clone(),
+                tags: entry.tags.clone(),
+                items: entry.items.clone(),
+            });
+        }
+        let tags: Vec<String> = entry.tags.iter().map(|name| settings.tag_style.render(name)).collect();
+        let mut properties = entry.properties.clone();
+        let tags = match settings.tag_placement {
+            format::TagPlacement::Inline => tags,
+            format::TagPlacement::Property => {
+                properties.insert(0, ("tags".to_owned(), tags.join(", ")));
+                Vec::new()
+            }
+        };
+        output += &journal_format.entry(&entry.title, &entry.site, &entry.url, &tags, &properties);
+    }
+
+    if !report.is_empty() {
+        println!("{}", report);
+        if let Some(path) = settings.report_path.as_ref().filter(|_| !dry_run) {
+            std::fs::write(path, report.to_string())?;
+        }
+    }
+
     if !output.is_empty() {
         let date = chrono::Utc::now().format("%Y_%m_%d").to_string();
-        let file_path = format!("{}{}.md", folder_path + "/journals/", date);
-    
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)?;
+        let file_path = format!("journals/{}.{}", date, journal_format.extension());
         println!("{:?}", &output);
-        file.write_all(output.as_bytes())?;
+        rewriter.append(Path::new(&file_path), &output)?;
+    }
+
+    if dry_run {
+        print!("{}", rewriter);
+        if is_data_input_from_pocket {
+            println!("Pocket actions not sent: {}", serde_json::to_string_pretty(&action)?);
+        }
+        return Ok(());
+    }
+
+    if let Some(run) = rewriter.logged_run() {
+        println!("Vault changes logged as run {}, revert them with `parser-rs undo {}`", run, run);
     }
-    
 
     if is_data_input_from_pocket {
         let url = Url::parse_with_params(
@@ -0,0 +1,96 @@
+use reqwest::Url;
+use serde::Deserialize;
+use crate::settings::Settings;
+
+/// What to do with the `#fragment` of a URL.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FragmentPolicy {
+    Drop,
+    Keep,
+    /// Keep fragments only where they route to a different page: on the
+    /// configured SPA hosts, and for `#/path` or `#!path` style routes.
+    KeepForSpaHosts,
+}
+
+/// Standards-based (RFC 3986) normalization run before any site rule, so
+/// links differing only in scheme or host case, default ports, dot segments,
+/// percent-encoding, IDN vs punycode, an empty `?` or the fragment compare equal.
+pub struct Normalizer {
+    fragment_policy: FragmentPolicy,
+    spa_hosts: Vec<String>,
+}
+
+impl Normalizer {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            fragment_policy: settings.fragment_policy,
+            spa_hosts: settings.spa_hosts.clone(),
+        }
+    }
+
+    /// Returns the normalized form of `url`, or `url` itself if it does not parse.
+    pub fn normalize(&self, url: &str) -> String {
+        // parsing lowercases the scheme and host, converts IDN hosts to
+        // punycode, drops default ports and resolves `.` and `..` segments
+        let mut parsed = match Url::parse(url.trim()) {
+            Ok(parsed) => parsed,
+            Err(_) => return url.to_owned(),
+        };
+        if parsed.cannot_be_a_base() {
+            return parsed.to_string();
+        }
+
+        let path = normalize_percent_encoding(parsed.path());
+        parsed.set_path(&path);
+        match parsed.query().map(normalize_percent_encoding) {
+            Some(query) if !query.is_empty() => parsed.set_query(Some(&query)),
+            _ => parsed.set_query(None),
+        }
+        let fragment = parsed
+            .fragment()
+            .map(normalize_percent_encoding)
+            .filter(|fragment| !fragment.is_empty() && self.keeps_fragment(&parsed, fragment));
+        parsed.set_fragment(fragment.as_deref());
+        parsed.to_string()
+    }
+
+    fn keeps_fragment(&self, url: &Url, fragment: &str) -> bool {
+        match self.fragment_policy {
+            FragmentPolicy::Drop => false,
+            FragmentPolicy::Keep => true,
+            FragmentPolicy::KeepForSpaHosts => {
+                let host = url.host_str().unwrap_or_default();
+                fragment.starts_with('/')
+                    || fragment.starts_with('!')
+                    || self.spa_hosts.iter().any(|spa| spa == host)
+            }
+        }
+    }
+}
+
+/// Uppercases the hex digits of percent-escapes and decodes escapes of
+/// unreserved characters (`A-Z a-z 0-9 - . _ ~`), which RFC 3986 defines as
+/// equivalent to the characters themselves.
+fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit));
+        if let Some(value) = escape.and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) {
+            if value.is_ascii_alphanumeric() || matches!(value, b'-' | b'.' | b'_' | b'~') {
+                output.push(value);
+            } else {
+                output.extend(format!("%{:02X}", value).bytes());
+            }
+            i += 3;
+            continue;
+        }
+        output.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
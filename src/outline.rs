@@ -0,0 +1,286 @@
+use std::ops::Range;
+use crate::format::Format;
+
+/// One block of a page: its bullet or headline, the lines continuing it,
+/// and the blocks nested under it.
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Indentation width or number of stars; see `Format::depth`.
+    pub depth: usize,
+    /// 0-based lines of the block itself: its first line, continuation and
+    /// property lines, up to its first child.
+    pub lines: Range<usize>,
+    pub children: Vec<usize>,
+    /// Name, value and line of each property of the block.
+    pub properties: Vec<(String, String, usize)>,
+}
+
+impl Block {
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(n, _, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value, _)| value.as_str())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.property("id")
+    }
+}
+
+/// A page split into blocks. Lines keep their own line endings, so a page
+/// edited through the outline is written back byte for byte except for
+/// the lines that were changed.
+pub struct Outline {
+    format: Format,
+    lines: Vec<String>,
+    /// Blocks in page order; `children` index into it.
+    blocks: Vec<Block>,
+}
+
+impl Outline {
+    pub fn parse(text: &str, format: Format) -> Self {
+        let lines: Vec<String> = text.split_inclusive('\n').map(str::to_owned).collect();
+        let mut blocks: Vec<Block> = Vec::new();
+        // blocks not closed yet, outermost first
+        let mut open: Vec<usize> = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let line = content(line);
+            let Some(depth) = format.depth(line) else {
+                continue;
+            };
+            while let Some(&last) = open.last() {
+                if blocks[last].depth < depth {
+                    break;
+                }
+                if blocks[last].children.is_empty() {
+                    blocks[last].lines.end = index;
+                }
+                open.pop();
+            }
+            let parent = open.last().copied();
+            let next = blocks.len();
+            if let Some(parent) = parent {
+                if blocks[parent].children.is_empty() {
+                    blocks[parent].lines.end = index;
+                }
+                blocks[parent].children.push(next);
+            }
+            open.push(next);
+            blocks.push(Block {
+                depth,
+                lines: index..lines.len(),
+                children: Vec::new(),
+                properties: Vec::new(),
+            });
+        }
+        let mut outline = Self { format, lines, blocks };
+        for index in 0..outline.blocks.len() {
+            outline.read_block(index);
+        }
+        outline
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// The block starting on 0-based line `line`.
+    pub fn at_line(&self, line: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| block.lines.start == line)
+    }
+
+    /// The block with the `id` property `id`.
+    pub fn with_id(&self, id: &str) -> Option<usize> {
+        self.blocks.iter().position(|block| block.id() == Some(id))
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Line `index` without its line ending.
+    pub fn line(&self, index: usize) -> &str {
+        content(&self.lines[index])
+    }
+
+    /// The block's own lines, joined by `\n`.
+    pub fn text(&self, block: usize) -> String {
+        self.blocks[block]
+            .lines
+            .clone()
+            .map(|index| self.line(index))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Replaces the content of line `index`, keeping its line ending.
+    pub fn set_line(&mut self, index: usize, text: &str) {
+        let ending = &self.lines[index][content(&self.lines[index]).len()..];
+        self.lines[index] = format!("{}{}", text, ending);
+        if let Some(block) = self.blocks.iter().rposition(|block| block.lines.contains(&index)) {
+            self.read_block(block);
+        }
+    }
+
+    /// Adds `values` to the comma-separated property `name` of the block,
+    /// creating the property after the block's last one, or after its first
+    /// line, when it has none.
+    pub fn add_to_property(&mut self, block: usize, name: &str, values: &[String]) {
+        if values.is_empty() {
+            return;
+        }
+        let values = values.join(", ");
+        let existing = self.blocks[block]
+            .properties
+            .iter()
+            .find(|(n, _, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value, line)| (value.is_empty(), *line));
+        if let Some((empty, line)) = existing {
+            let separator = if empty { " " } else { ", " };
+            let text = format!("{}{}{}", self.line(line).trim_end(), separator, values);
+            self.set_line(line, &text);
+            return;
+        }
+        let first = self.blocks[block].lines.start;
+        let last = self.blocks[block].properties.last().map(|(_, _, line)| *line);
+        match self.format {
+            Format::Markdown => {
+                let indent = &self.line(first)[..self.line(first).len() - self.line(first).trim_start().len()];
+                let text = format!("{}  {}:: {}", indent, name, values);
+                self.insert_lines(last.unwrap_or(first) + 1, &[text]);
+            }
+            Format::Org => {
+                let text = format!(":{}: {}", name, values);
+                let drawer = self.blocks[block]
+                    .lines
+                    .clone()
+                    .find(|&line| self.line(line).trim().eq_ignore_ascii_case(":PROPERTIES:"));
+                match (drawer, last) {
+                    (Some(_), Some(last)) => self.insert_lines(last + 1, &[text]),
+                    (Some(drawer), None) => self.insert_lines(drawer + 1, &[text]),
+                    (None, _) => self.insert_lines(first + 1, &[":PROPERTIES:".to_owned(), text, ":END:".to_owned()]),
+                }
+            }
+        }
+    }
+
+    /// Inserts `texts` as lines before line `index`, with the line ending of
+    /// the line above, and parses the page again.
+    fn insert_lines(&mut self, index: usize, texts: &[String]) {
+        let ending = match index.checked_sub(1).map(|above| &self.lines[above]) {
+            Some(above) if above.ends_with("\r\n") => "\r\n",
+            Some(above) if above.ends_with('\n') => "\n",
+            // the last line of the page
+            _ if self.lines.iter().any(|line| line.ends_with("\r\n")) => "\r\n",
+            _ => "\n",
+        };
+        let mut lines: Vec<String> = texts.iter().map(|text| format!("{}{}", text, ending)).collect();
+        if index == self.lines.len() {
+            // the page's last line may lack a line ending, and keeps lacking one
+            if let Some(previous) = self.lines.last_mut() {
+                if content(previous).len() == previous.len() {
+                    previous.push_str(ending);
+                    if let Some(last) = lines.last_mut() {
+                        last.truncate(last.len() - ending.len());
+                    }
+                }
+            }
+        }
+        self.lines.splice(index..index, lines);
+        *self = Self::parse(&self.to_string(), self.format);
+    }
+
+    /// Whether the block is a flashcard: a `;;` card, a multi-line card
+    /// with a `?` line, or a Logseq `#card`.
+    pub fn is_card(&self, block: usize) -> bool {
+        let text = self.text(block);
+        text.contains(";;")
+            || text.lines().skip(1).any(|line| line.trim() == "?")
+            || self.format.tags(&text).iter().any(|tag| tag.eq_ignore_ascii_case("card"))
+            || self.blocks[block].properties.iter().any(|(name, _, _)| name.starts_with("card-"))
+    }
+
+    fn read_block(&mut self, index: usize) {
+        let block = &self.blocks[index];
+        let mut properties = Vec::new();
+        for line in block.lines.clone() {
+            let text = content(&self.lines[line]);
+            if line > block.lines.start {
+                if let Some((name, value)) = self.format.property_entry(text) {
+                    properties.push((name.to_owned(), value.to_owned(), line));
+                }
+            }
+        }
+        self.blocks[index].properties = properties;
+    }
+}
+
+impl std::fmt::Display for Outline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            f.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// `line` without its `\n` or `\r\n`.
+fn content(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags() -> Vec<String> {
+        vec!["rust".to_owned()]
+    }
+
+    #[test]
+    fn pages_are_written_back_byte_for_byte() {
+        for page in ["- a\n  - b\n- c\n", "- a\r\n  - b\r\n- c", "title:: Page\n\n- a\n", ""] {
+            assert_eq!(Outline::parse(page, Format::Markdown).to_string(), page);
+        }
+    }
+
+    #[test]
+    fn blocks_nest_by_depth() {
+        let outline = Outline::parse("- a\n  more\n  - b\n    id:: 1\n- c\n", Format::Markdown);
+        let blocks = outline.blocks();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].lines, 0..2);
+        assert_eq!(blocks[0].children, [1]);
+        assert_eq!(outline.with_id("1"), Some(1));
+        assert_eq!(outline.at_line(4), Some(2));
+        assert_eq!(outline.text(0), "- a\n  more");
+    }
+
+    #[test]
+    fn properties_keep_line_endings() {
+        let mut outline = Outline::parse("- a\r\n  - b\r\n", Format::Markdown);
+        outline.add_to_property(1, "tags", &tags());
+        assert_eq!(outline.to_string(), "- a\r\n  - b\r\n    tags:: rust\r\n");
+
+        let mut outline = Outline::parse("- a\n- b", Format::Markdown);
+        outline.add_to_property(1, "tags", &tags());
+        assert_eq!(outline.to_string(), "- a\n- b\n  tags:: rust");
+        outline.add_to_property(1, "tags", &["go".to_owned()]);
+        assert_eq!(outline.to_string(), "- a\n- b\n  tags:: rust, go");
+    }
+
+    #[test]
+    fn org_properties_go_in_a_drawer() {
+        let mut outline = Outline::parse("* a\n** b\n", Format::Org);
+        outline.add_to_property(1, "tags", &tags());
+        assert_eq!(outline.to_string(), "* a\n** b\n:PROPERTIES:\n:tags: rust\n:END:\n");
+        assert_eq!(outline.blocks()[1].property("tags"), Some("rust"));
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use chrono::{Datelike, NaiveDate};
+use percent_encoding::percent_decode_str;
+use crate::format::Format;
+use crate::index::Location;
+
+/// A place an item of this run was already saved.
+pub struct Found {
+    pub location: Location,
+    /// Date of the journal page, when the link is in one.
+    pub journal_date: Option<NaiveDate>,
+    /// Tags of the block before this run touched it.
+    pub tags: Vec<String>,
+}
+
+impl Found {
+    pub fn new(location: Location) -> Self {
+        let journal_date = journal_date(&location.file);
+        let tags = Format::of(&location.file).tags(&location.block);
+        Self { location, journal_date, tags }
+    }
+}
+
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "already saved")?;
+        if let Some(date) = self.journal_date {
+            write!(f, " on {}", date)?;
+        }
+        write!(f, " in {}:{}", self.location.file.display(), self.location.line_number)?;
+        if !self.tags.is_empty() {
+            write!(f, " with tags {}", self.tags.join(", "))?;
+        }
+        for line in self.location.block.lines() {
+            write!(f, "\n    > {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// An item left out of the journal because the vault already has it.
+pub struct Duplicate {
+    pub title: String,
+    pub url: String,
+    pub found: Vec<Found>,
+}
+
+/// A new item whose title is close to one already saved under another URL.
+pub struct NearDuplicate {
+    pub title: String,
+    pub url: String,
+    pub similarity: f64,
+    pub found: Found,
+}
+
+/// Items of this run written as a single entry because they are the same link.
+pub struct Merge {
+    pub title: String,
+    pub url: String,
+    pub tags: Vec<String>,
+    /// Title and URL of each merged item.
+    pub items: Vec<(String, String)>,
+}
+
+/// What a run did with the items it was given, beyond the journal output.
+#[derive(Default)]
+pub struct Report {
+    pub duplicates: Vec<Duplicate>,
+    pub merges: Vec<Merge>,
+    pub near_duplicates: Vec<NearDuplicate>,
+    /// Files whose tags were not added because another program kept
+    /// changing them while they were edited.
+    pub conflicts: Vec<PathBuf>,
+    /// Files whose tags were not added because they are not valid UTF-8.
+    pub unreadable: Vec<PathBuf>,
+    /// Saved links whose tags were not added because they are in no block.
+    pub untagged: Vec<Location>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty()
+            && self.merges.is_empty()
+            && self.near_duplicates.is_empty()
+            && self.conflicts.is_empty()
+            && self.unreadable.is_empty()
+            && self.untagged.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for duplicate in &self.duplicates {
+            writeln!(f, "{} ({})", duplicate.title, duplicate.url)?;
+            for found in &duplicate.found {
+                writeln!(f, "  {}", found)?;
+            }
+        }
+        for merge in &self.merges {
+            writeln!(f, "{} ({})", merge.title, merge.url)?;
+            write!(f, "  merged {} items of this run", merge.items.len())?;
+            if !merge.tags.is_empty() {
+                write!(f, " with tags {}", merge.tags.join(", "))?;
+            }
+            writeln!(f)?;
+            for (title, url) in &merge.items {
+                writeln!(f, "    < {} ({})", title, url)?;
+            }
+        }
+        for near in &self.near_duplicates {
+            writeln!(f, "{} ({})", near.title, near.url)?;
+            writeln!(f, "  written, but {:.0}% like a block {}", near.similarity * 100.0, near.found)?;
+        }
+        for file in &self.conflicts {
+            writeln!(f, "{}", file.display())?;
+            writeln!(f, "  left untouched, it changed while tags were added to it")?;
+        }
+        for file in &self.unreadable {
+            writeln!(f, "{}", file.display())?;
+            writeln!(f, "  left untouched, it is not valid UTF-8")?;
+        }
+        for location in &self.untagged {
+            writeln!(f, "{}:{}", location.file.display(), location.line_number)?;
+            writeln!(f, "  found, but tags not added, the link is not in a block")?;
+            writeln!(f, "    > {}", location.line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Logseq link to the block at `location`: a block reference when it has
+/// an id, otherwise a link to its page.
+pub fn block_link(location: &Location) -> String {
+    if let Some(id) = &location.id {
+        return format!("(({}))", id);
+    }
+    let page = match journal_date(&location.file) {
+        // Logseq's default journal title format, `MMM do, yyyy`
+        Some(date) => {
+            let day = date.day();
+            let suffix = match (day % 10, day % 100) {
+                (_, 11..=13) => "th",
+                (1, _) => "st",
+                (2, _) => "nd",
+                (3, _) => "rd",
+                _ => "th",
+            };
+            format!("{} {}{}, {}", date.format("%b"), day, suffix, date.year())
+        }
+        None => {
+            let stem = location.file.file_stem().unwrap_or_default().to_string_lossy();
+            percent_decode_str(&stem.replace("___", "/")).decode_utf8_lossy().into_owned()
+        }
+    };
+    format!("[[{}]]", page)
+}
+
+/// Date of a journal page from its file name, e.g. `journals/2024_03_02.md`.
+fn journal_date(file: &Path) -> Option<NaiveDate> {
+    if file.parent()?.file_name()? != "journals" {
+        return None;
+    }
+    let stem = file.file_stem()?.to_str()?;
+    ["%Y_%m_%d", "%Y-%m-%d", "%Y%m%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(stem, format).ok())
+}
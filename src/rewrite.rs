@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use crate::settings::Settings;
+use crate::undo::{self, Record};
+
+/// Replaces vault files so that a crash or a full disk leaves either the
+/// old or the new contents, never a mix, and the old contents are kept.
+/// In a dry run nothing is written; the changes are kept to be shown.
+pub struct Rewriter {
+    root: PathBuf,
+    /// Id of this run in backups and the undo log.
+    run: String,
+    /// Folder backups go in, one subfolder per run; none when unset.
+    backups: Option<PathBuf>,
+    /// Undo log every change is recorded in, once it is made.
+    undo_log: Option<PathBuf>,
+    /// Whether a change of this run was logged.
+    logged: bool,
+    /// While this file exists, say because Logseq is open, nothing is written.
+    lock: Option<PathBuf>,
+    /// SHA-256 of each file as last read or written by this run, to notice
+    /// Logseq or a sync client changing it in between.
+    hashes: HashMap<PathBuf, String>,
+    /// Files not replaced because they changed since they were read.
+    conflicts: Vec<PathBuf>,
+    /// Files not edited because they are not valid UTF-8.
+    unreadable: Vec<PathBuf>,
+    /// Contents before and after the run of each file a dry run changed.
+    dry_run: Option<BTreeMap<PathBuf, (String, String)>>,
+}
+
+impl Rewriter {
+    /// Backs files up to `settings.backup_path`, taken from the vault
+    /// folder `root` when relative, and logs changes to `settings.undo_log_path`.
+    pub fn new(root: &Path, settings: &Settings) -> Self {
+        let run = chrono::Local::now().format("%Y-%m-%dT%H-%M-%S-%3f").to_string();
+        Self {
+            root: root.to_path_buf(),
+            backups: settings.backup_path.as_ref().map(|backups| root.join(backups).join(&run)),
+            undo_log: settings.undo_log_path.as_ref().map(PathBuf::from),
+            run,
+            logged: false,
+            lock: settings.lock_path.as_ref().map(|lock| root.join(lock)),
+            hashes: HashMap::new(),
+            conflicts: Vec::new(),
+            unreadable: Vec::new(),
+            dry_run: None,
+        }
+    }
+
+    /// A rewriter that leaves the vault untouched.
+    pub fn dry_run(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            run: String::new(),
+            backups: None,
+            undo_log: None,
+            logged: false,
+            lock: None,
+            hashes: HashMap::new(),
+            conflicts: Vec::new(),
+            unreadable: Vec::new(),
+            dry_run: Some(BTreeMap::new()),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Id of this run in the undo log, once it logged a change.
+    pub fn logged_run(&self) -> Option<&str> {
+        Some(self.run.as_str()).filter(|_| self.logged)
+    }
+
+    /// Files left unchanged because another program changed them while
+    /// this run was editing them.
+    pub fn conflicts(&self) -> &[PathBuf] {
+        &self.conflicts
+    }
+
+    /// Files left unchanged because they are not valid UTF-8, which an
+    /// edit could not keep byte for byte.
+    pub fn unreadable(&self) -> &[PathBuf] {
+        &self.unreadable
+    }
+
+    /// Fails while the lock file exists.
+    pub fn check_lock(&self) -> Result<(), Error> {
+        match &self.lock {
+            Some(lock) if lock.exists() => Err(Error::other(format!(
+                "The vault is locked by {}, remove it once Logseq is closed",
+                lock.display()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Contents of `file`, relative to the vault folder, with the changes
+    /// of a dry run. A missing file is empty, one that is not UTF-8 an
+    /// `InvalidData` error. A later `replace` of the file fails with a
+    /// `Conflict` if it changes in between.
+    pub fn read(&mut self, file: &Path) -> Result<String, Error> {
+        if let Some((_, contents)) = self.dry_run.as_ref().and_then(|changes| changes.get(file)) {
+            return Ok(contents.clone());
+        }
+        match fs::read_to_string(self.root.join(file)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => {
+                if e.kind() == ErrorKind::InvalidData && !self.unreadable.iter().any(|f| f == file) {
+                    self.unreadable.push(file.to_path_buf());
+                }
+                Err(e)
+            }
+            Ok(contents) => {
+                self.hashes.insert(file.to_path_buf(), hash(contents.as_bytes()));
+                Ok(contents)
+            }
+        }
+    }
+
+    /// Adds `text` at the end of `file`, creating it if needed.
+    pub fn append(&mut self, file: &Path, text: &str) -> Result<(), Error> {
+        if self.dry_run.is_some() {
+            let contents = self.read(file)? + text;
+            return self.replace(file, &contents);
+        }
+        self.check_lock()?;
+        let path = self.root.join(file);
+        let original = match fs::read_to_string(&path) {
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            result => Some(result?),
+        };
+        let mut appended = OpenOptions::new().create(true).append(true).open(path)?;
+        appended.write_all(text.as_bytes())?;
+        appended.sync_all()?;
+        let contents = original.clone().unwrap_or_default() + text;
+        self.log(file, original, contents)
+    }
+
+    /// Deletes `file`, after backing it up.
+    pub fn remove(&mut self, file: &Path) -> Result<(), Error> {
+        if self.dry_run.is_some() {
+            return self.replace(file, "");
+        }
+        self.check_lock()?;
+        self.verify(file)?;
+        self.back_up(file)?;
+        fs::remove_file(self.root.join(file))
+    }
+
+    /// Writes `contents` to a temporary file beside `file`, relative to the
+    /// vault folder, syncs it and renames it over `file`, keeping the
+    /// file's permissions. The file is backed up first. Fails with a
+    /// `Conflict`, leaving the file alone, if it changed since it was read.
+    pub fn replace(&mut self, file: &Path, contents: &str) -> Result<(), Error> {
+        if self.dry_run.is_some() {
+            let original = self.read(file)?;
+            if let Some(changes) = &mut self.dry_run {
+                changes.entry(file.to_path_buf()).or_insert((original, String::new())).1 = contents.to_owned();
+            }
+            return Ok(());
+        }
+        let path = self.root.join(file);
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        self.check_lock()?;
+        let original = self.verify(file)?;
+        let metadata = fs::metadata(&path)?;
+        self.back_up(file)?;
+
+        // in the same folder, so the rename never crosses filesystems
+        let mut temp_file = NamedTempFile::new_in(dir)?;
+        temp_file.write_all(contents.as_bytes())?;
+        temp_file.as_file().set_permissions(metadata.permissions())?;
+        temp_file.as_file().sync_all()?;
+        // once more, as writing took time
+        self.verify(file)?;
+        temp_file.persist(&path).map_err(|e| e.error)?;
+        sync_dir(dir)?;
+        self.log(file, Some(original), contents.to_owned())?;
+        self.hashes.insert(file.to_path_buf(), hash(contents.as_bytes()));
+        self.conflicts.retain(|conflict| conflict != file);
+        Ok(())
+    }
+
+    /// Current contents of `file`, or a `Conflict` when they are not what
+    /// this run last read or wrote.
+    fn verify(&mut self, file: &Path) -> Result<String, Error> {
+        let contents = fs::read_to_string(self.root.join(file))?;
+        match self.hashes.get(file) {
+            Some(known) if *known != hash(contents.as_bytes()) => {
+                if !self.conflicts.iter().any(|conflict| conflict == file) {
+                    self.conflicts.push(file.to_path_buf());
+                }
+                Err(Error::other(Conflict(file.to_path_buf())))
+            }
+            _ => Ok(contents),
+        }
+    }
+
+    fn back_up(&self, file: &Path) -> Result<(), Error> {
+        if let Some(backups) = &self.backups {
+            let backup = backups.join(file);
+            // a file rewritten twice in a run keeps its contents from before the run
+            if !backup.exists() {
+                fs::create_dir_all(backup.parent().unwrap_or(backups))?;
+                fs::copy(self.root.join(file), &backup)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn log(&mut self, file: &Path, original: Option<String>, contents: String) -> Result<(), Error> {
+        let Some(undo_log) = &self.undo_log else {
+            return Ok(());
+        };
+        let record = Record {
+            run: self.run.clone(),
+            file: file.to_path_buf(),
+            original,
+            contents,
+        };
+        undo::append(undo_log, &record)?;
+        self.logged = true;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Rewriter {
+    /// A unified diff of every file a dry run would change.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (file, (original, contents)) in self.dry_run.iter().flatten() {
+            if original == contents {
+                continue;
+            }
+            let file = file.display().to_string();
+            let patch = diffy::DiffOptions::new()
+                .set_original_filename(format!("a/{}", file))
+                .set_modified_filename(format!("b/{}", file))
+                .create_patch(original, contents);
+            write!(f, "{}", patch)?;
+        }
+        Ok(())
+    }
+}
+
+/// A file changed on disk after it was read, by Logseq or a sync client.
+#[derive(Debug)]
+pub struct Conflict(pub PathBuf);
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} changed while it was being edited", self.0.display())
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+pub fn is_conflict(e: &Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<Conflict>())
+}
+
+fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Makes the rename itself durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<(), Error> {
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<(), Error> {
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use crate::settings::Settings;
+
+/// Which vault files are scanned, by globs relative to the vault folder.
+pub struct Scope {
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl Scope {
+    pub fn new(settings: &Settings) -> Result<Self, globset::Error> {
+        Ok(Self {
+            include: glob_set(&settings.scan_include)?,
+            exclude: glob_set(&settings.scan_exclude)?,
+        })
+    }
+
+    /// Whether `file`, relative to the vault folder, is scanned.
+    pub fn contains(&self, file: &Path) -> bool {
+        self.include.is_match(file) && !self.exclude.is_match(file)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        // `*` stays within one directory, `**` crosses them
+        let glob: Glob = GlobBuilder::new(pattern).literal_separator(true).build()?;
+        builder.add(glob);
+    }
+    builder.build()
+}
+
+/// Whether `file` is a copy a sync client left beside a file edited in two
+/// places: Dropbox `(conflicted copy ...)`, Syncthing `.sync-conflict-...`
+/// or Nextcloud `_conflict-...`. These are never modified.
+pub fn is_conflict_copy(file: &Path) -> bool {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)\(.*conflicted copy.*\)|\.sync-conflict-\d{8}-\d{6}|_conflict-\d{8}-\d{6}").unwrap()
+    });
+    file.file_name()
+        .is_some_and(|name| pattern.is_match(&name.to_string_lossy()))
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::scope::Scope;
+
+/// How a needle is matched against vault lines. Both are smart-case like fzf:
+/// case-insensitive unless the needle contains an uppercase letter.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchMode {
+    /// The needle appears as a substring of the line.
+    Exact,
+    /// The characters of the needle appear in order, not necessarily adjacent.
+    Fuzzy,
+}
+
+/// A line of a vault file containing a needle, i.e. one `file:line` result.
+#[derive(Debug, Clone)]
+pub struct Match {
+    /// Path relative to the vault folder.
+    pub file: PathBuf,
+    /// 1-based line number.
+    pub line_number: usize,
+    pub line: String,
+}
+
+impl std::fmt::Display for Match {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line_number, self.line)
+    }
+}
+
+/// A vault file with a lowercased copy for case-insensitive searches.
+struct VaultFile {
+    path: PathBuf,
+    contents: String,
+    lowercase: String,
+}
+
+impl VaultFile {
+    fn new(path: PathBuf, contents: String) -> Self {
+        let lowercase = contents.to_lowercase();
+        Self { path, contents, lowercase }
+    }
+}
+
+/// The text files of a vault, read once and searched in memory.
+pub struct Vault {
+    files: Vec<VaultFile>,
+    mode: MatchMode,
+}
+
+impl Vault {
+    /// Reads every text file in `scope`, binary files being ignored.
+    pub fn load(root: &str, mode: MatchMode, scope: &Scope) -> Result<Self, Error> {
+        let root = PathBuf::from(root);
+        let mut files = Vec::new();
+        for path in vault_files(&root)? {
+            let relative = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+            if !scope.contains(&relative) {
+                continue;
+            }
+            if let Some(contents) = read_text(&path)? {
+                files.push(VaultFile::new(relative, contents));
+            }
+        }
+        Ok(Self { files, mode })
+    }
+
+    /// Every line matching `needle`, in file order.
+    pub fn search(&self, needle: &str) -> Vec<Match> {
+        let case_sensitive = needle.chars().any(char::is_uppercase);
+        let needle = if case_sensitive { needle.to_owned() } else { needle.to_lowercase() };
+        let mut matches = Vec::new();
+        for file in &self.files {
+            let haystack = if case_sensitive { &file.contents } else { &file.lowercase };
+            if self.mode == MatchMode::Exact && !haystack.contains(&needle) {
+                continue;
+            }
+            for (index, (line, searched)) in file.contents.lines().zip(haystack.lines()).enumerate() {
+                let found = match self.mode {
+                    MatchMode::Exact => searched.contains(&needle),
+                    MatchMode::Fuzzy => is_subsequence(&needle, searched),
+                };
+                if found {
+                    matches.push(Match {
+                        file: file.path.clone(),
+                        line_number: index + 1,
+                        line: line.to_owned(),
+                    });
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// Every file below `root`, sorted. Like `grep -r "" *`, hidden entries
+/// directly in `root` are skipped.
+pub fn vault_files(root: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with('.') {
+            collect_files(&entry.path(), &mut paths)?;
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn collect_files(path: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let file_type = fs::symlink_metadata(path)?.file_type();
+    if file_type.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect_files(&entry?.path(), paths)?;
+        }
+    } else if file_type.is_file() {
+        paths.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Contents of a text file, or `None` for binary files (those containing NUL).
+fn read_text(path: &Path) -> Result<Option<String>, Error> {
+    let bytes = fs::read(path)?;
+    if bytes.contains(&0) {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|n| chars.any(|h| h == n))
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::env;
+use serde::Deserialize;
+use crate::format::{Format, TagPlacement, TagStyle};
+use crate::normalize::FragmentPolicy;
+use crate::search::MatchMode;
+
+/// Optional tool settings, read from `settings.json` (or the file named by the
+/// `settingsPath` env var). Every field falls back to its default when missing.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// Registrable domain (or full host) -> label used in `-[site](url)`.
+    pub site_aliases: HashMap<String, String>,
+    /// Entity type (e.g. `zhihu/answer`) -> tag added to links of that type.
+    pub entity_tags: HashMap<String, String>,
+    /// Groups of interchangeable URL prefixes (without scheme or `www.`),
+    /// the first one being canonical. See `Equivalence`.
+    pub equivalent_prefixes: Vec<Vec<String>>,
+    /// Treat `m.` and `mobile.` subdomains as the desktop site.
+    pub mobile_subdomains: bool,
+    /// `drop`, `keep` or `keepForSpaHosts`.
+    pub fragment_policy: FragmentPolicy,
+    /// Hosts whose fragments route to different pages, kept under `keepForSpaHosts`.
+    pub spa_hosts: Vec<String>,
+    /// How `parser-rs search <text>` matches vault lines: `exact` or `fuzzy`.
+    pub match_mode: MatchMode,
+    /// File the vault link index is kept in between runs.
+    pub index_path: String,
+    /// Globs, relative to the vault folder, of the files scanned for links.
+    pub scan_include: Vec<String>,
+    /// Globs of files never scanned nor modified, such as Logseq's backups.
+    pub scan_exclude: Vec<String>,
+    /// Flag new items whose title is at least this similar (0 to 1) to the
+    /// title of a saved block. Off when unset.
+    pub near_duplicate_threshold: Option<f64>,
+    /// File the run report (items already in the vault) is also written to.
+    pub report_path: Option<String>,
+    /// `markdown` or `org` for new journal entries; when unset, the graph's
+    /// `:preferred-format` is used.
+    pub journal_format: Option<Format>,
+    /// `bracketed` (`#[[x]]`), `hash` (`#x`) or `pageRef` (`[[x]]`), for the
+    /// tags of new entries and the ones added to saved blocks.
+    pub tag_style: TagStyle,
+    /// `inline` after the link, or `property` to merge tags into the block's
+    /// `tags::` property.
+    pub tag_placement: TagPlacement,
+    /// Folder, relative to the vault folder unless absolute, where files are
+    /// copied before they are rewritten, in a subfolder per run. No backups
+    /// when `null`.
+    pub backup_path: Option<String>,
+    /// File every change to the vault is logged in, for `parser-rs undo`.
+    /// No log when `null`.
+    pub undo_log_path: Option<String>,
+    /// File, relative to the vault folder unless absolute, whose presence
+    /// stops the tool from writing to the vault, e.g. while Logseq is open.
+    pub lock_path: Option<String>,
+    /// How many times an edit is made again on the fresh contents of a file
+    /// that changed while it was being edited, before the file is skipped.
+    pub conflict_retries: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let site_aliases = [
+            ("zhihu.com", "知乎"),
+            ("x.com", "twitter"),
+            ("twitter.com", "twitter"),
+        ]
+        .iter()
+        .map(|(domain, label)| (domain.to_string(), label.to_string()))
+        .collect();
+        let equivalent_prefixes = [
+            &["weibo.com", "weibo.cn", "m.weibo.cn"][..],
+            &["reddit.com", "old.reddit.com", "new.reddit.com", "np.reddit.com", "m.reddit.com"],
+            &["en.wikipedia.org", "en.m.wikipedia.org"],
+            &["x.com", "twitter.com", "mobile.twitter.com", "nitter.net"],
+            &["youtube.com", "m.youtube.com"],
+        ]
+        .iter()
+        .map(|group| group.iter().map(|prefix| prefix.to_string()).collect())
+        .collect();
+        Self {
+            site_aliases,
+            entity_tags: HashMap::new(),
+            equivalent_prefixes,
+            mobile_subdomains: true,
+            fragment_policy: FragmentPolicy::KeepForSpaHosts,
+            spa_hosts: to_strings(&["mail.google.com", "app.slack.com", "web.telegram.org", "www.notion.so"]),
+            match_mode: MatchMode::Exact,
+            index_path: "url-index.json".to_owned(),
+            scan_include: to_strings(&["**/*.md", "**/*.markdown", "**/*.org"]),
+            scan_exclude: to_strings(&["logseq/bak/**", "logseq/.recycle/**", "**/.git/**", "assets/**"]),
+            near_duplicate_threshold: None,
+            report_path: None,
+            journal_format: None,
+            tag_style: TagStyle::Bracketed,
+            tag_placement: TagPlacement::Inline,
+            backup_path: Some("logseq/bak/parser-rs".to_owned()),
+            undo_log_path: Some("undo-log.jsonl".to_owned()),
+            lock_path: Some("parser-rs.lock".to_owned()),
+            conflict_retries: 2,
+        }
+    }
+}
+
+fn to_strings(strs: &[&str]) -> Vec<String> {
+    strs.iter().map(|str| str.to_string()).collect()
+}
+
+pub fn load() -> Result<Settings, Box<dyn std::error::Error>> {
+    let path = env::var("settingsPath").unwrap_or_else(|_| "settings.json".to_owned());
+    match std::fs::read_to_string(&path) {
+        Ok(str) => Ok(serde_json::from_str(&str)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+        Err(e) => Err(e.into()),
+    }
+}
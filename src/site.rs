@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use publicsuffix::{List, Psl};
+use reqwest::Url;
+use url::Host;
+
+static PUBLIC_SUFFIX_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+fn list() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| PUBLIC_SUFFIX_LIST.parse().expect("Failed to parse public suffix list"))
+}
+
+/// Registrable domain of `host`, e.g. `bbc.co.uk` for `news.bbc.co.uk`.
+pub fn registrable_domain(host: &str) -> Option<String> {
+    let host = host.trim_end_matches('.').to_lowercase();
+    let domain = list().domain(host.as_bytes())?;
+    Some(String::from_utf8_lossy(domain.as_bytes()).into_owned())
+}
+
+/// Label shown as `-[site](url)` in journal lines.
+///
+/// An alias for the full host wins over one for the registrable domain;
+/// otherwise the first label of the registrable domain is used, so
+/// `news.ycombinator.com` gives `ycombinator` and `m.weibo.cn` gives `weibo`.
+pub fn label(url: &str, aliases: &HashMap<String, String>) -> String {
+    let host = match Url::parse(url).ok().and_then(|u| u.host().map(|h| h.to_owned())) {
+        Some(Host::Domain(host)) => host.to_lowercase(),
+        // IP addresses have no registrable domain
+        Some(host) => return host.to_string(),
+        None => return String::new(),
+    };
+    let host = host.trim_start_matches("www.").to_owned();
+    if let Some(alias) = aliases.get(&host) {
+        return alias.clone();
+    }
+    let domain = match registrable_domain(&host) {
+        Some(domain) => domain,
+        // single-label hosts such as `localhost`
+        None => return host,
+    };
+    if let Some(alias) = aliases.get(&domain) {
+        return alias.clone();
+    }
+    domain.split('.').next().unwrap_or_default().to_owned()
+}
@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use regex::Regex;
+
+/// Fewer shingles than this are too little text to call two titles alike.
+const MIN_SHINGLES: usize = 3;
+
+struct Patterns {
+    /// `-[site](url)` and `-[[url][site]]` after titles written by this tool.
+    site_link: Regex,
+    tag: Regex,
+    markdown_link: Regex,
+    org_link: Regex,
+    url: Regex,
+    headline_tags: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        site_link: Regex::new(r"-\[[^\]]*\]\([^)]*\)|-\[\[[^\]]*\]\[[^\]]*\]\]").unwrap(),
+        tag: Regex::new(r"(?:^|\s)#(?:\[\[[^\]]*\]\]|\S+)").unwrap(),
+        markdown_link: Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").unwrap(),
+        org_link: Regex::new(r"\[\[[^\]]*\](?:\[([^\]]*)\])?\]").unwrap(),
+        url: Regex::new(r"https?://\S+").unwrap(),
+        headline_tags: Regex::new(r"\s:[^\s:]+(?::[^\s:]+)*:\s*$").unwrap(),
+    })
+}
+
+/// The title of a block from its first line: link texts kept, bullets,
+/// tags, URLs, site labels and the `;;` card marker dropped.
+pub fn from_block(line: &str) -> String {
+    let patterns = patterns();
+    let line = line.trim_start().trim_start_matches(['-', '*']).trim();
+    let line = patterns.headline_tags.replace(line, "");
+    let line = patterns.site_link.replace_all(&line, "");
+    let line = patterns.tag.replace_all(&line, " ");
+    let line = patterns.markdown_link.replace_all(&line, "$1");
+    let line = patterns.org_link.replace_all(&line, "$1");
+    let line = patterns.url.replace_all(&line, " ");
+    line.replace(";;", " ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Lowercased words of a title, with runs of CJK characters (written
+/// without spaces) split into overlapping character pairs instead.
+pub fn shingles(title: &str) -> HashSet<String> {
+    let mut shingles = HashSet::new();
+    let mut word = String::new();
+    let mut cjk: Vec<char> = Vec::new();
+    for c in title.to_lowercase().chars().chain([' ']) {
+        if is_cjk(c) {
+            push_word(&mut shingles, &mut word);
+            cjk.push(c);
+            continue;
+        }
+        push_cjk(&mut shingles, &mut cjk);
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            push_word(&mut shingles, &mut word);
+        }
+    }
+    shingles
+}
+
+/// Jaccard similarity of two shingle sets, 0 when either is too short.
+pub fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.len() < MIN_SHINGLES || b.len() < MIN_SHINGLES {
+        return 0.0;
+    }
+    let common = a.intersection(b).count();
+    common as f64 / (a.len() + b.len() - common) as f64
+}
+
+fn push_word(shingles: &mut HashSet<String>, word: &mut String) {
+    if !word.is_empty() {
+        shingles.insert(std::mem::take(word));
+    }
+}
+
+fn push_cjk(shingles: &mut HashSet<String>, run: &mut Vec<char>) {
+    match run.len() {
+        0 => return,
+        1 => {
+            shingles.insert(run[0].to_string());
+        }
+        _ => {
+            for pair in run.windows(2) {
+                shingles.insert(pair.iter().collect());
+            }
+        }
+    }
+    run.clear();
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'     // hiragana, katakana
+        | '\u{3400}'..='\u{4dbf}'   // CJK extension A
+        | '\u{4e00}'..='\u{9fff}'   // CJK unified ideographs
+        | '\u{ac00}'..='\u{d7af}'   // hangul syllables
+        | '\u{f900}'..='\u{faff}'   // CJK compatibility ideographs
+        | '\u{20000}'..='\u{2fa1f}' // CJK extensions B-F
+    )
+}
@@ -0,0 +1,184 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::rewrite::Rewriter;
+
+/// One change a run made to a vault file.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    pub run: String,
+    /// Path relative to the vault folder.
+    pub file: PathBuf,
+    /// Contents before the change; `None` when the run created the file.
+    pub original: Option<String>,
+    pub contents: String,
+}
+
+/// Adds `record` to the log at `path`, one JSON object per line.
+pub fn append(path: &Path, record: &Record) -> Result<(), Error> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_all()
+}
+
+fn load(path: &Path) -> Result<Vec<Record>, Error> {
+    let log = match fs::read_to_string(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        result => result?,
+    };
+    log.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Error::from))
+        .collect()
+}
+
+/// Runs in the log at `path`, oldest first, with the files each changed.
+pub fn runs(path: &Path) -> Result<Vec<(String, Vec<PathBuf>)>, Error> {
+    let mut runs: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    for record in load(path)? {
+        let index = match runs.iter().position(|(run, _)| *run == record.run) {
+            Some(index) => index,
+            None => {
+                runs.push((record.run.clone(), Vec::new()));
+                runs.len() - 1
+            }
+        };
+        if !runs[index].1.contains(&record.file) {
+            runs[index].1.push(record.file);
+        }
+    }
+    Ok(runs)
+}
+
+/// What undoing a run did to one file.
+pub enum Outcome {
+    Reverted,
+    /// The file changed since the run; the run's change was taken out of it
+    /// and the later edits kept.
+    Merged,
+    /// The file changed since the run in the lines the run changed.
+    Conflict,
+    Removed,
+    Unchanged,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Reverted => "reverted",
+            Self::Merged => "reverted, keeping the edits made since",
+            Self::Conflict => "left as is, it was edited since in the same lines",
+            Self::Removed => "removed, the run created it",
+            Self::Unchanged => "unchanged",
+        })
+    }
+}
+
+/// `current` with the changes of `records`, oldest first, taken out of it
+/// newest first, each merged three ways so edits other programs made
+/// between them are kept. Also tells whether a merge was needed; `None`
+/// when a change overlaps a later edit.
+fn revert<'a>(current: &str, records: impl DoubleEndedIterator<Item = &'a Record>) -> Option<(String, bool)> {
+    let mut text = current.to_owned();
+    let mut merged = false;
+    for record in records.rev() {
+        let original = record.original.as_deref().unwrap_or_default();
+        if text == record.contents {
+            text = original.to_owned();
+        } else {
+            text = diffy::merge(&record.contents, &text, original).ok()?;
+            merged = true;
+        }
+    }
+    Some((text, merged))
+}
+
+/// Reverts the changes `run` made, from the log at `path`, through
+/// `rewriter`. A file edited since is merged three ways, and left alone
+/// when the edits overlap.
+pub fn undo(path: &Path, run: &str, rewriter: &mut Rewriter) -> Result<Vec<(PathBuf, Outcome)>, Error> {
+    let records: Vec<Record> = load(path)?.into_iter().filter(|record| record.run == run).collect();
+    if records.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, format!("no run {} in {}", run, path.display())));
+    }
+    let mut outcomes: Vec<(PathBuf, Outcome)> = Vec::new();
+    for record in &records {
+        if outcomes.iter().any(|(file, _)| *file == record.file) {
+            continue;
+        }
+        if !rewriter.root().join(&record.file).exists() {
+            outcomes.push((record.file.clone(), Outcome::Unchanged));
+            continue;
+        }
+        let current = rewriter.read(&record.file)?;
+        let outcome = match revert(&current, records.iter().filter(|r| r.file == record.file)) {
+            None => Outcome::Conflict,
+            Some((text, _)) if text == current => Outcome::Unchanged,
+            // the first change of the run created the file
+            Some((text, merged)) if text.is_empty() && record.original.is_none() && !merged => {
+                rewriter.remove(&record.file)?;
+                Outcome::Removed
+            }
+            Some((text, merged)) => {
+                rewriter.replace(&record.file, &text)?;
+                if merged {
+                    Outcome::Merged
+                } else {
+                    Outcome::Reverted
+                }
+            }
+        };
+        outcomes.push((record.file.clone(), outcome));
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(original: Option<&str>, contents: &str) -> Record {
+        Record {
+            run: "run".to_owned(),
+            file: PathBuf::from("pages/a.md"),
+            original: original.map(str::to_owned),
+            contents: contents.to_owned(),
+        }
+    }
+
+    #[test]
+    fn untouched_files_are_reverted() {
+        let records = [record(Some("- a\n- b\n"), "- a #x\n- b\n"), record(Some("- a #x\n- b\n"), "- a #x\n- b #y\n")];
+        assert_eq!(revert("- a #x\n- b #y\n", records.iter()), Some(("- a\n- b\n".to_owned(), false)));
+        let created = [record(None, "- a\n")];
+        assert_eq!(revert("- a\n", created.iter()), Some((String::new(), false)));
+    }
+
+    #[test]
+    fn edits_made_since_are_kept() {
+        let records = [record(Some("- a\n- b\n- c\n"), "- a #x\n- b\n- c\n")];
+        let current = "- a #x\n- b\n- c\n- d\n";
+        assert_eq!(revert(current, records.iter()), Some(("- a\n- b\n- c\n- d\n".to_owned(), true)));
+    }
+
+    #[test]
+    fn edits_between_changes_are_kept() {
+        let records = [
+            record(Some("- a\n- b\n- c\n- d\n"), "- a #x\n- b\n- c\n- d\n"),
+            // `- c` was edited by hand between the two changes
+            record(Some("- a #x\n- b\n- c!\n- d\n"), "- a #x\n- b\n- c!\n- d #y\n"),
+        ];
+        let current = "- a #x\n- b\n- c!\n- d #y\n";
+        assert_eq!(revert(current, records.iter()), Some(("- a\n- b\n- c!\n- d\n".to_owned(), true)));
+    }
+
+    #[test]
+    fn overlapping_edits_are_refused() {
+        let records = [record(Some("- a\n- b\n"), "- a #x\n- b\n")];
+        assert_eq!(revert("- a #x #z\n- b\n", records.iter()), None);
+    }
+}
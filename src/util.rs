@@ -1,118 +1,141 @@
+use std::path::Path;
+use std::io::{Error, ErrorKind};
+use crate::format::{Format, TagPlacement};
+use crate::index::{Index, Location};
+use crate::outline::Outline;
+use crate::rewrite::{self, Rewriter};
+use crate::settings::Settings;
 
-use std::env;
-use std::fs::File;
-use std::io::{prelude::*, BufReader, BufWriter};
-use std::path::PathBuf;
-use reqwest::{Client, Method, Url};
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::process::Command;
-use std::io::Error;
-use regex::Regex;
-use tempfile::NamedTempFile;
-
-pub fn execute_command(highlights_string: &str, folder_path: &str) -> Result<String, Error> {
-    let cmd = format!("grep --line-buffered --color=never -r \"\" * | fzf --filter=\"'{}\"", highlights_string);
-    // print the command
-    println!("{}", cmd);
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(&cmd)
-        .current_dir(folder_path) // Set the current directory to folder_path
-        .output()?;
-
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-    } else {
-        Err(Error::new(std::io::ErrorKind::Other, "Error execute Command"))
+/// Finds the blocks saving one of `keys` and adds the tags named `tags`
+/// they do not carry yet, placed and written as `settings` say, replacing
+/// each file through `rewriter`. Links outside of any block, such as in a
+/// paragraph or a page property, are left alone and added to `untagged`.
+/// Returns the blocks as they were before the edit, or a `NotFound` error
+/// when no block saves the link; any other error is a failure to edit.
+pub fn check_and_reset(index: &mut Index, rewriter: &mut Rewriter, keys: &[String], tags: &[String], settings: &Settings, untagged: &mut Vec<Location>) -> Result<Vec<Location>, Error> {
+    let locations = index.find(keys);
+    if locations.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, "URL not found in vault"));
+    }
+    let mut files: Vec<&Path> = Vec::new();
+    for found in &locations {
+        if !files.contains(&found.file.as_path()) {
+            files.push(&found.file);
+        }
     }
+    for file in files {
+        let mut attempt = 0;
+        loop {
+            match edit(index, rewriter, file, keys, tags, settings, untagged) {
+                // changed by Logseq or a sync client meanwhile: edit the new contents
+                Err(e) if rewrite::is_conflict(&e) && attempt < settings.conflict_retries => {
+                    attempt += 1;
+                    let contents = rewriter.read(file).map_err(|e| in_file(file, e))?;
+                    index.refresh(file, &contents).map_err(|e| in_file(file, e))?;
+                }
+                // left for the report, see `Rewriter::conflicts` and `Rewriter::unreadable`
+                Err(e) if rewrite::is_conflict(&e) || e.kind() == ErrorKind::InvalidData => break,
+                result => {
+                    result.map_err(|e| in_file(file, e))?;
+                    break;
+                }
+            }
+        }
+    }
+    Ok(locations)
 }
 
-pub fn check_and_reset(folder_path: &str, url: &str, tags: &Vec<String>) -> Result<(), Error> {
-    let search_result = execute_command(&("(".to_owned() + &url + ")"), &folder_path);
-        match search_result {
-            Ok(res) => {
-                if !res.is_empty() {
-                    // The URL was found in a file in the folder
-                    let lines: Vec<&str> = res.split('\n').collect();
-                    for line in lines {
-                        let file_path_and_line_content: Vec<&str> = line.splitn(2, ':').collect();
-                        if file_path_and_line_content.len() >= 2 {
-                            let file_path = file_path_and_line_content[0];
-                            let line_content = file_path_and_line_content[1];
-                            // Open the file
-                            let mut full_path = PathBuf::from(folder_path.clone());
-                            full_path.push(file_path);
+/// `e` with the file it happened in, and never `NotFound`, which callers
+/// take as the link not being in the vault.
+fn in_file(file: &Path, e: Error) -> Error {
+    Error::other(format!("{}: {}", file.display(), e))
+}
 
-                            let file = File::open(&full_path).unwrap();
-                            let reader = BufReader::new(file);
-                            // Create a temporary file
-                            let mut temp_file = NamedTempFile::new().unwrap();
-                            {
-                                let mut writer = BufWriter::new(&temp_file);
-                                // Read the file line by line
-                                let mut lines = reader.lines().peekable();
-                                while let Some(line) = lines.next() {
-                                    let line = line.unwrap();
-                                    if line == line_content {
-                                        // Modify the line
-                                        // let re = Regex::new(r"<!--SR:![^>]*-->").unwrap();
-                                        let mut modified_line = line;
-                                        // Append non-existent tags to the line
-                                        for tag in tags {
-                                            if !modified_line.contains(tag) {
-                                                modified_line = format!("{} {}", modified_line, tag);
-                                            }
-                                        }
-                                        let mut is_card = false;
-                                        // check if the line is card
-                                        if modified_line.contains(";;") {
-                                            is_card = true;
-                                        }
-                                        // if the next line have "?"
-                                        if let Some(Ok(next_line)) = lines.peek() {
-                                            if next_line.contains("?") {
-                                                is_card = true;
-                                            }
-                                        }
-                                        if !is_card {
-                                            modified_line = format!("{} ;; ", modified_line);
-                                        }
-                                
-                                        writeln!(writer, "{}", modified_line);
-                                    } else {
-                                        // Write the original line
-                                        writeln!(writer, "{}", line);
-                                    }
-                                }
-                            }
-                            // Replace the original file with the temporary file
-                            temp_file.persist(full_path);
-                        }
-                    }
-                } else {
-                    // impossible
-                }
-            },
-            Err(e) => {
-                return Err(e);
-            }
+/// Adds the tags to the blocks of `file` saving one of `keys`.
+fn edit(index: &mut Index, rewriter: &mut Rewriter, file: &Path, keys: &[String], tags: &[String], settings: &Settings, untagged: &mut Vec<Location>) -> Result<(), Error> {
+    let mut outline = Outline::parse(&rewriter.read(file)?, Format::of(file));
+    let mut blocks: Vec<usize> = Vec::new();
+    for found in index.find(keys).into_iter().filter(|found| found.file == file) {
+        // the index was just refreshed, but skip blocks edited since
+        if let Some(block) = locate(&outline, &found) {
+            blocks.push(block);
+        } else if outside_blocks(&outline, &found)
+            && tags.iter().any(|name| !outline.format().has_tag(&found.line, name))
+            && !untagged.iter().any(|u| u.file == found.file && u.line_number == found.line_number)
+        {
+            untagged.push(found);
         }
+    }
+    // bottom up, so lines added to a block do not move the ones left
+    blocks.sort_unstable_by(|a, b| b.cmp(a));
+    blocks.dedup();
+    let mut changed = false;
+    for block in blocks {
+        changed |= add_tags(&mut outline, block, tags, settings);
+    }
+    if changed {
+        let contents = outline.to_string();
+        rewriter.replace(file, &contents)?;
+        index.refresh(file, &contents)?;
+    }
     Ok(())
 }
 
-pub fn check(folder_path: &str, url: &str, tags: &Vec<String>) -> Result<(), Error> {
-    let search_result = execute_command(&("(".to_owned() + &url + ")"), &folder_path);
-        match search_result {
-            Ok(res) => {
-                
-            },
-            Err(e) => {
-                return Err(e);
-            }
+/// Finds the blocks saving one of `keys`, without changing them.
+pub fn check(index: &Index, keys: &[String], _tags: &[String]) -> Result<Vec<Location>, Error> {
+    let locations = index.find(keys);
+    if locations.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, "URL not found in vault"));
+    }
+    Ok(locations)
+}
+
+/// Adds the tags named `tags` the block does not carry yet, and the `;;`
+/// card marker unless it is a card already. Returns whether it changed.
+fn add_tags(outline: &mut Outline, block: usize, tags: &[String], settings: &Settings) -> bool {
+    let format = outline.format();
+    let line = outline.blocks()[block].lines.start;
+    let text = outline.text(block);
+    let missing: Vec<String> = tags
+        .iter()
+        .filter(|name| !format.has_tag(&text, name))
+        .map(|name| settings.tag_style.render(name))
+        .collect();
+    let mut modified_line = outline.line(line).to_owned();
+    if settings.tag_placement == TagPlacement::Inline {
+        for tag in &missing {
+            modified_line = format.append(&modified_line, tag);
         }
-    Ok(())
-}
\ No newline at end of file
+    }
+    if !outline.is_card(block) {
+        modified_line = format.append(&modified_line, ";; ");
+    }
+    let changed = modified_line != outline.line(line) || !missing.is_empty();
+    outline.set_line(line, &modified_line);
+    if settings.tag_placement == TagPlacement::Property {
+        outline.add_to_property(block, "tags", &missing);
+    }
+    changed
+}
+
+/// Whether `found` is still on its line, and that line is in no block.
+fn outside_blocks(outline: &Outline, found: &Location) -> bool {
+    let Some(line) = found.line_number.checked_sub(1) else {
+        return false;
+    };
+    line < outline.line_count()
+        && outline.line(line) == found.line
+        && outline.blocks().iter().all(|block| !block.lines.contains(&line))
+}
+
+/// The block `found` points to: the one with its id, else the one still
+/// starting with the same line at the same place.
+fn locate(outline: &Outline, found: &Location) -> Option<usize> {
+    if let Some(block) = found.id.as_deref().and_then(|id| outline.with_id(id)) {
+        return Some(block);
+    }
+    let line = found.line_number.checked_sub(1)?;
+    outline
+        .at_line(line)
+        .filter(|_| outline.line(line) == found.line)
+}